@@ -0,0 +1,49 @@
+use crate::domain::interfaces::{FileStorageTrait, WebSocketTrait};
+use crate::domain::models::AppState;
+use crate::infrastructure::protocol::ClientMessage;
+use crate::services::barge_in::BargeIn;
+use crate::services::vad::VadTransition;
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::error::RecvError;
+
+/// Watches the capture VAD and interrupts TTS playback when the user talks over it.
+pub fn spawn_barge_in_monitor(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let audio_service = state.audio_service.clone();
+        let playback = state.playback_service.clone();
+        let websocket = state.websocket.clone();
+        let events = state.events.clone();
+        let storage = state.storage.clone();
+        let mut speech = audio_service.subscribe_speech();
+        let mut barge_in = BargeIn::default();
+
+        loop {
+            let transition = match speech.recv().await {
+                Ok(transition) => transition,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+
+            if transition == VadTransition::SpeechStart {
+                // Pick up settings changes without restarting the app
+                if let Ok(settings) = storage.load_settings() {
+                    barge_in.set_settings(settings.audio.barge_in);
+                }
+            }
+
+            let Some(action) = barge_in.on_speech(transition, playback.is_playing()) else {
+                continue;
+            };
+            let Some(event) = barge_in.apply(action, &playback) else {
+                continue;
+            };
+
+            log::info!("User barged in during TTS playback ({:?})", event.mode);
+            let _ = events.emit("barge-in", event);
+            if let Err(e) = websocket.send_message(ClientMessage::Interrupt.to_text()).await {
+                log::warn!("Could not send interrupt to Lily-Core: {}", e);
+            }
+        }
+    });
+}
@@ -1,10 +1,16 @@
+use crate::domain::error::LilyError;
 use crate::domain::interfaces::{FileStorageTrait, WebSocketTrait};
-use crate::domain::models::{AppSettings, AppState, ChatMessage, LogEntry, TTSParameters, WebSocketStatus};
-use crate::infrastructure::file_storage::FileStorage;
-use crate::infrastructure::websocket::WebSocketService;
-use reqwest;
+use crate::domain::models::{AppSettings, AppState, ChatMessage, LogEntry, StorageRecovered, TTSParameters, WebSocketStatus};
+use crate::infrastructure::lily_core::{AgentLoop, ChatRequest, ChatResponse, MonitoringSnapshot};
+use crate::infrastructure::protocol::ClientMessage;
+use crate::services::audio_frames::FrameStatsSnapshot;
+use crate::services::audio_meter::AudioMeterReading;
+use crate::services::audio_pipeline::StreamSettings;
+use crate::services::audio_playback_service::{PcmFormat, PlaybackStatus};
+use crate::services::audio_service::InputDeviceSelection;
+use crate::services::vad::VadSettings;
 use serde_json;
-use tauri::{AppHandle, State};
+use tauri::State;
 
 #[tauri::command]
 pub fn greet(name: &str) -> String {
@@ -12,175 +18,135 @@ pub fn greet(name: &str) -> String {
 }
 
 #[tauri::command]
-pub fn save_settings(settings: AppSettings) -> Result<(), String> {
-    FileStorage::save_settings(settings)
+pub fn save_settings(settings: AppSettings, state: State<'_, AppState>) -> Result<(), LilyError> {
+    settings.audio.stream.validate()?;
+    state.storage.save_settings(settings)
 }
 
 #[tauri::command]
-pub fn load_settings() -> Result<AppSettings, String> {
-    FileStorage::load_settings()
+pub fn load_settings(state: State<'_, AppState>) -> Result<AppSettings, LilyError> {
+    state.storage.load_settings()
 }
 
 #[tauri::command]
-pub async fn connect_websocket(app_handle: AppHandle) -> Result<(), String> {
-    WebSocketService::connect(app_handle).await
+pub async fn connect_websocket(state: State<'_, AppState>) -> Result<(), LilyError> {
+    state.websocket.connect().await
 }
 
 #[tauri::command]
-pub async fn disconnect_websocket(app_handle: AppHandle) -> Result<(), String> {
-    WebSocketService::disconnect(app_handle).await
+pub async fn disconnect_websocket(state: State<'_, AppState>) -> Result<(), LilyError> {
+    state.websocket.disconnect().await
 }
 
 #[tauri::command]
-pub async fn send_websocket_message(message: String, app_handle: AppHandle) -> Result<(), String> {
-    WebSocketService::send_message(message, app_handle).await
+pub async fn send_websocket_message(message: String, state: State<'_, AppState>) -> Result<(), LilyError> {
+    state.websocket.send_message(message).await
 }
 
 #[tauri::command]
-pub fn save_chat_history(messages: Vec<ChatMessage>) -> Result<(), String> {
-    FileStorage::save_chat_history(messages)
+pub fn save_chat_history(messages: Vec<ChatMessage>, state: State<'_, AppState>) -> Result<(), LilyError> {
+    state.storage.save_chat_history(messages)
 }
 
 #[tauri::command]
-pub fn load_chat_history() -> Result<Vec<ChatMessage>, String> {
-    FileStorage::load_chat_history()
+pub fn load_chat_history(state: State<'_, AppState>) -> Result<Vec<ChatMessage>, LilyError> {
+    state.storage.load_chat_history()
 }
 
 #[tauri::command]
-pub fn clear_chat_history() -> Result<(), String> {
-    FileStorage::clear_chat_history()
+pub fn clear_chat_history(state: State<'_, AppState>) -> Result<(), LilyError> {
+    state.storage.clear_chat_history()
 }
 
 #[tauri::command]
-pub fn add_log_entry(type_: String, message: String, details: Option<serde_json::Value>) -> Result<(), String> {
-    FileStorage::add_log_entry(type_, message, details)
+pub fn add_log_entry(type_: String, message: String, details: Option<serde_json::Value>, state: State<'_, AppState>) -> Result<(), LilyError> {
+    state.storage.add_log_entry(type_, message, details)
 }
 
 #[tauri::command]
-pub fn get_logs() -> Result<Vec<LogEntry>, String> {
-    FileStorage::get_logs()
+pub fn get_logs(state: State<'_, AppState>) -> Result<Vec<LogEntry>, LilyError> {
+    state.storage.get_logs()
 }
 
 #[tauri::command]
-pub fn clear_logs() -> Result<(), String> {
-    FileStorage::clear_logs()
+pub fn clear_logs(state: State<'_, AppState>) -> Result<(), LilyError> {
+    state.storage.clear_logs()
 }
 
-#[tauri::command]
-pub async fn send_chat_message(message: String, tts_enabled: bool, tts_params: Option<TTSParameters>) -> Result<serde_json::Value, String> {
-    let client = reqwest::Client::new();
-    let mut request_body = serde_json::json!({
-        "message": message,
-        "user_id": "default_user"
-    });
+fn chat_request(message: String, tts_enabled: bool, tts_params: Option<TTSParameters>, state: &AppState) -> ChatRequest {
+    let tts_params = tts_enabled.then(|| tts_params.unwrap_or_default());
 
-    if tts_enabled {
-        if let Some(params) = tts_params {
-            request_body["tts"] = serde_json::json!({
-                "enabled": true,
-                "params": params
-            });
-        } else {
-            request_body["tts"] = serde_json::json!({
-                "enabled": true,
-                "params": {
-                    "speaker": 0,
-                    "sample_rate": 24000,
-                    "model": "edge",
-                    "lang": "en-US"
-                }
-            });
-        }
+    // Replies without a WAV header come back as PCM16 at the requested rate
+    if let Some(tts_params) = tts_params.as_ref().filter(|params| params.sample_rate > 0) {
+        state.playback_service.set_raw_format(PcmFormat {
+            sample_rate: tts_params.sample_rate as u32,
+            channels: 1,
+        });
     }
 
-    let response = client.post("http://localhost:8000/chat")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
-
-    if !response.status().is_success() {
-        return Err(format!("HTTP error! status: {}", response.status()));
-    }
-
-    let data: serde_json::Value = response.json()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-
-    Ok(data)
+    ChatRequest::new(message, tts_params)
 }
 
+/// Files restored from backup since the last call; later ones also arrive as `storage-recovered`.
 #[tauri::command]
-pub async fn get_conversation_history() -> Result<Vec<ChatMessage>, String> {
-    let client = reqwest::Client::new();
-    let response = client.get("http://localhost:8000/conversation/default_user")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
-
-    if !response.status().is_success() {
-        return Err(format!("HTTP error! status: {}", response.status()));
-    }
-
-    let data: serde_json::Value = response.json()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-
-    let messages = data["conversation"].as_array()
-        .ok_or("Invalid response format: missing conversation array")?
-        .iter()
-        .map(|msg| {
-            Ok(ChatMessage {
-                role: msg["role"].as_str().unwrap_or("assistant").to_string(),
-                content: msg["content"].as_str().unwrap_or("").to_string(),
-                timestamp: msg["timestamp"].as_str().unwrap_or("").to_string(),
-            })
-        })
-        .collect::<Result<Vec<ChatMessage>, String>>()?;
-
-    Ok(messages)
+pub fn take_storage_recoveries(state: State<'_, AppState>) -> Vec<StorageRecovered> {
+    state.storage.take_recoveries()
 }
 
+/// Pass a `request_id` to be able to cancel the request while it runs.
 #[tauri::command]
-pub async fn clear_conversation() -> Result<(), String> {
-    let client = reqwest::Client::new();
-    let response = client.delete("http://localhost:8000/conversation/default_user")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
+pub async fn send_chat_message(
+    message: String,
+    tts_enabled: bool,
+    tts_params: Option<TTSParameters>,
+    request_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<ChatResponse, LilyError> {
+    let request = chat_request(message, tts_enabled, tts_params, &state);
+    let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    state.chat.send(request_id, request).await
+}
 
-    if !response.status().is_success() {
-        return Err(format!("HTTP error! status: {}", response.status()));
-    }
+/// Streams the reply as `chat-delta` events and returns their message id.
+/// Async so the stream is spawned on the Tokio runtime.
+#[tauri::command]
+pub async fn stream_chat_message(message: String, tts_enabled: bool, tts_params: Option<TTSParameters>, state: State<'_, AppState>) -> Result<String, LilyError> {
+    let request = chat_request(message, tts_enabled, tts_params, &state);
+    Ok(state.chat.stream(request))
+}
 
-    Ok(())
+/// Returns false if the request already finished.
+#[tauri::command]
+pub async fn cancel_chat_request(request_id: String, state: State<'_, AppState>) -> Result<bool, LilyError> {
+    Ok(state.chat.cancel(&request_id))
 }
 
 #[tauri::command]
-pub async fn get_monitoring_data() -> Result<serde_json::Value, String> {
-    let client = reqwest::Client::new();
-    let response = client.get("http://localhost:8000/monitoring")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
+pub async fn get_conversation_history(state: State<'_, AppState>) -> Result<Vec<ChatMessage>, LilyError> {
+    state.lily_core.get_conversation().await
+}
 
-    if !response.status().is_success() {
-        return Err(format!("HTTP error! status: {}", response.status()));
-    }
+#[tauri::command]
+pub async fn clear_conversation(state: State<'_, AppState>) -> Result<(), LilyError> {
+    state.lily_core.clear_conversation().await
+}
 
-    let data: serde_json::Value = response.json()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
+#[tauri::command]
+pub async fn get_monitoring_data(state: State<'_, AppState>) -> Result<MonitoringSnapshot, LilyError> {
+    state.lily_core.get_monitoring().await
+}
 
-    Ok(data)
+#[tauri::command]
+pub async fn get_agent_loop(state: State<'_, AppState>) -> Result<AgentLoop, LilyError> {
+    state.lily_core.get_agent_loop().await
 }
 
 #[tauri::command]
-pub async fn send_websocket_audio(audio_data: Vec<u8>, app_handle: AppHandle) -> Result<(), String> {
+pub async fn send_websocket_audio(audio_data: Vec<u8>, state: State<'_, AppState>) -> Result<(), LilyError> {
     log::debug!("Audio data detected, size: {}", audio_data.len());
     log::info!("send_websocket_audio command received - Audio data size: {} bytes", audio_data.len());
     
-    let result = WebSocketService::send_binary_data(audio_data, app_handle).await;
+    let result = state.websocket.send_binary_data(audio_data).await;
     
     match &result {
         Ok(_) => log::info!("send_websocket_audio command completed successfully"),
@@ -191,32 +157,141 @@ pub async fn send_websocket_audio(audio_data: Vec<u8>, app_handle: AppHandle) ->
 }
 
 #[tauri::command]
-pub async fn get_websocket_status(app_handle: AppHandle) -> Result<WebSocketStatus, String> {
-    WebSocketService::get_status(app_handle).await
+pub async fn get_websocket_status(state: State<'_, AppState>) -> Result<WebSocketStatus, LilyError> {
+    Ok(state.websocket.get_status().await)
+}
+
+#[tauri::command]
+pub async fn start_audio_recording(device_name: Option<String>, state: State<'_, AppState>) -> Result<InputDeviceSelection, LilyError> {
+    let audio_settings = state.storage.load_settings().map(|settings| settings.audio).unwrap_or_default();
+
+    // An explicit device wins; otherwise use the persisted default microphone
+    let device_name = device_name.or(audio_settings.input_device);
+    log::info!("Starting audio recording (requested device: {:?})", device_name);
+
+    state.audio_service.set_stream_settings(audio_settings.stream);
+    state.audio_service.set_vad_settings(audio_settings.vad);
+    state.audio_service.set_echo_settings(audio_settings.echo);
+
+    // Announce the format before the first frame is queued so the server can decode it
+    let handshake = ClientMessage::AudioStart(audio_settings.stream.format());
+    if let Err(e) = state.websocket.send_message(handshake.to_text()).await {
+        log::warn!("Could not send audio format handshake: {}", e);
+    }
+
+    let selection = state.audio_service.start_recording_with_device(device_name).await?;
+
+    if selection.fell_back {
+        log::warn!(
+            "Input device {:?} not found, recording from default device '{}' instead",
+            selection.requested, selection.device
+        );
+        let _ = state.events.emit("audio-device-fallback", &selection);
+    }
+
+    Ok(selection)
+}
+
+#[tauri::command]
+pub fn set_audio_input_device(device_name: Option<String>, state: State<'_, AppState>) -> Result<(), LilyError> {
+    let mut settings = state.storage.load_settings()?;
+    settings.audio.input_device = device_name;
+    state.storage.save_settings(settings)
+}
+
+#[tauri::command]
+pub fn set_vad_settings(settings: VadSettings, state: State<'_, AppState>) -> Result<(), LilyError> {
+    let mut app_settings = state.storage.load_settings()?;
+    app_settings.audio.vad = settings;
+    state.storage.save_settings(app_settings)?;
+    state.audio_service.set_vad_settings(settings);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_stream_settings(settings: StreamSettings, state: State<'_, AppState>) -> Result<(), LilyError> {
+    settings.validate()?;
+    let mut app_settings = state.storage.load_settings()?;
+    app_settings.audio.stream = settings;
+    state.storage.save_settings(app_settings)?;
+    state.audio_service.set_stream_settings(settings);
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn start_audio_recording(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
-    log::info!("Starting audio recording");
-    state.audio_service.set_app_handle(app_handle);
-    state.audio_service.start_recording().await
+pub fn set_audio_output_device(device_name: Option<String>, state: State<'_, AppState>) -> Result<(), LilyError> {
+    let mut settings = state.storage.load_settings()?;
+    settings.audio.output_device = device_name.clone();
+    state.storage.save_settings(settings)?;
+    state.playback_service.set_output_device(device_name);
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn stop_audio_recording(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn stop_audio_recording(state: State<'_, AppState>) -> Result<(), LilyError> {
     log::info!("Stopping audio recording");
     state.audio_service.stop_recording().await
 }
 
 #[tauri::command]
-pub async fn get_audio_level(_state: State<'_, AppState>) -> Result<f32, String> {
-    // This would return the current RMS level, but for now we'll return 0.0
-    // In a real implementation, we'd want to get the latest value from the audio service
-    Ok(0.0)
+pub async fn pause_audio_recording(state: State<'_, AppState>) -> Result<(), LilyError> {
+    log::info!("Pausing audio recording");
+    state.audio_service.pause_recording().await
+}
+
+#[tauri::command]
+pub async fn resume_audio_recording(state: State<'_, AppState>) -> Result<(), LilyError> {
+    log::info!("Resuming audio recording");
+    state.audio_service.resume_recording().await
+}
+
+#[tauri::command]
+pub async fn get_audio_level(state: State<'_, AppState>) -> Result<f32, LilyError> {
+    Ok(state.audio_service.audio_level())
+}
+
+#[tauri::command]
+pub async fn get_audio_meter(state: State<'_, AppState>) -> Result<AudioMeterReading, LilyError> {
+    Ok(state.audio_service.audio_meter())
+}
+
+#[tauri::command]
+pub fn get_audio_stream_stats(state: State<'_, AppState>) -> FrameStatsSnapshot {
+    state.audio_service.frame_stats()
+}
+
+#[tauri::command]
+pub fn pause_tts_playback(state: State<'_, AppState>) {
+    state.playback_service.pause();
+}
+
+#[tauri::command]
+pub fn resume_tts_playback(state: State<'_, AppState>) {
+    state.playback_service.resume();
+}
+
+#[tauri::command]
+pub fn skip_tts_playback(state: State<'_, AppState>) {
+    state.playback_service.skip();
+}
+
+#[tauri::command]
+pub fn stop_tts_playback(state: State<'_, AppState>) {
+    state.playback_service.stop();
+}
+
+#[tauri::command]
+pub fn set_tts_volume(volume: f32, state: State<'_, AppState>) {
+    state.playback_service.set_volume(volume);
+}
+
+#[tauri::command]
+pub fn get_tts_playback_status(state: State<'_, AppState>) -> PlaybackStatus {
+    state.playback_service.status()
 }
 
 #[tauri::command]
-pub async fn get_audio_devices(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+pub async fn get_audio_devices(state: State<'_, AppState>) -> Result<Vec<String>, LilyError> {
     log::info!("Getting available audio devices");
     state.audio_service.get_available_devices()
 }
\ No newline at end of file
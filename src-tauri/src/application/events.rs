@@ -0,0 +1,22 @@
+use crate::services::events::EventSink;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter};
+
+/// Forwards service events to the webview.
+pub struct TauriEventSink {
+    app_handle: AppHandle,
+}
+
+impl TauriEventSink {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self { app_handle }
+    }
+}
+
+impl EventSink for TauriEventSink {
+    fn emit_value(&self, event: &str, payload: Value) -> Result<(), String> {
+        self.app_handle
+            .emit(event, payload)
+            .map_err(|e| format!("Failed to emit {}: {}", event, e))
+    }
+}
@@ -1 +1,106 @@
-pub mod commands;
\ No newline at end of file
+pub mod barge_in;
+pub mod commands;
+pub mod events;
+
+use crate::domain::interfaces::FileStorageTrait;
+use crate::domain::models::AppState;
+use crate::infrastructure::lily_core::LilyCoreClient;
+use crate::infrastructure::sqlite_storage::SqliteStorage;
+use crate::infrastructure::storage_paths::StoragePaths;
+use crate::infrastructure::websocket::WebSocketService;
+use crate::services::audio_playback_service::AudioPlaybackService;
+use crate::services::audio_service::AudioService;
+use crate::services::chat::ChatService;
+use crate::services::events::EventSink;
+use events::TauriEventSink;
+use std::sync::Arc;
+use tauri::Manager;
+
+/// Wires the core services into a Tauri app and runs it.
+pub fn run() {
+    env_logger::init();
+
+    let paths = StoragePaths::from_args(std::env::args().skip(1)).expect("could not determine the data directory");
+    log::info!("Storing data in {}", paths.root().display());
+
+    let playback_service = AudioPlaybackService::new();
+    let audio_service = AudioService::new();
+    audio_service.set_echo_reference(playback_service.echo_reference());
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_fs::init())
+        .setup(move |app| {
+            // Services report to the webview once there is an app handle to emit through
+            let events: Arc<dyn EventSink> = Arc::new(TauriEventSink::new(app.handle().clone()));
+            let storage: Arc<dyn FileStorageTrait> = Arc::new(SqliteStorage::new(paths).with_event_sink(events.clone()));
+            if let Ok(settings) = storage.load_settings() {
+                playback_service.set_output_device(settings.audio.output_device);
+            }
+            playback_service.set_event_sink(events.clone());
+            audio_service.set_event_sink(events.clone());
+
+            let playback_service = Arc::new(playback_service);
+            let websocket = WebSocketService::new(events.clone())
+                .with_playback(playback_service.clone())
+                .with_storage(storage.clone());
+            audio_service.set_frame_output(Arc::new(websocket.clone()));
+
+            let lily_core = LilyCoreClient::new().with_storage(storage.clone());
+            app.manage(AppState {
+                storage,
+                websocket,
+                chat: ChatService::new(lily_core.clone(), events.clone()),
+                lily_core,
+                events,
+                audio_service: Arc::new(audio_service),
+                playback_service,
+            });
+            barge_in::spawn_barge_in_monitor(app.handle().clone());
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            commands::greet,
+            commands::save_settings,
+            commands::load_settings,
+            commands::connect_websocket,
+            commands::disconnect_websocket,
+            commands::send_websocket_message,
+            commands::save_chat_history,
+            commands::load_chat_history,
+            commands::clear_chat_history,
+            commands::add_log_entry,
+            commands::get_logs,
+            commands::clear_logs,
+            commands::take_storage_recoveries,
+            commands::send_chat_message,
+            commands::stream_chat_message,
+            commands::cancel_chat_request,
+            commands::get_conversation_history,
+            commands::clear_conversation,
+            commands::get_monitoring_data,
+            commands::get_agent_loop,
+            commands::send_websocket_audio,
+            commands::get_websocket_status,
+            commands::start_audio_recording,
+            commands::stop_audio_recording,
+            commands::pause_audio_recording,
+            commands::resume_audio_recording,
+            commands::get_audio_level,
+            commands::get_audio_meter,
+            commands::get_audio_stream_stats,
+            commands::get_audio_devices,
+            commands::set_audio_input_device,
+            commands::set_vad_settings,
+            commands::set_stream_settings,
+            commands::set_audio_output_device,
+            commands::pause_tts_playback,
+            commands::resume_tts_playback,
+            commands::skip_tts_playback,
+            commands::stop_tts_playback,
+            commands::set_tts_volume,
+            commands::get_tts_playback_status
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
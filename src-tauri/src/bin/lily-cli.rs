@@ -0,0 +1,442 @@
+// Terminal client for Lily-Core built on the same library as the desktop app.
+// Build with `--no-default-features --features audio` to skip Tauri.
+use clap::{Parser, Subcommand};
+use lily_ui_lib::domain::error::LilyError;
+use lily_ui_lib::domain::interfaces::{FileStorageTrait, WebSocketTrait};
+use lily_ui_lib::domain::models::{AppSettings, ChatMessage, LogEntry, ServerSettings};
+use lily_ui_lib::infrastructure::lily_core::{AgentLoop, ChatRequest, LilyCoreClient, MonitoringSnapshot};
+use lily_ui_lib::infrastructure::protocol::ClientMessage;
+use lily_ui_lib::infrastructure::sqlite_storage::SqliteStorage;
+use lily_ui_lib::infrastructure::storage_paths::StoragePaths;
+use lily_ui_lib::infrastructure::websocket::WebSocketService;
+use lily_ui_lib::services::audio_source::WavFileSource;
+use lily_ui_lib::services::chat::ChatService;
+use lily_ui_lib::services::events::{ChannelEventSink, EmittedEvent};
+use lily_ui_lib::AudioService;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{timeout, Duration, Instant};
+
+#[derive(Parser, Debug)]
+#[command(name = "lily-cli", about = "Drive Lily-Core from the terminal")]
+struct Cli {
+    /// Print results and events as JSON, one document per line
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Lily-Core WebSocket URL, overriding the saved settings
+    #[arg(long, global = true)]
+    ws_url: Option<String>,
+
+    /// Lily-Core HTTP base URL, overriding the saved settings
+    #[arg(long, global = true)]
+    http_url: Option<String>,
+
+    /// Keep settings, history and logs here (overrides LILY_UI_DATA_DIR)
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+
+    /// Keep data next to the executable
+    #[arg(long, global = true, conflicts_with = "data_dir")]
+    portable: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send a chat message and print the reply
+    Chat {
+        message: String,
+        /// Ask Lily-Core for a spoken reply using the saved TTS parameters
+        #[arg(long)]
+        tts: bool,
+        /// Print the reply as it is generated; Ctrl-C cancels it
+        #[arg(long)]
+        stream: bool,
+    },
+    /// Print the conversation history
+    History {
+        /// Read the locally saved chat history instead of Lily-Core's
+        #[arg(long)]
+        local: bool,
+    },
+    /// Clear the conversation history
+    Clear {
+        /// Clear the locally saved chat history instead of Lily-Core's
+        #[arg(long)]
+        local: bool,
+    },
+    /// Print Lily-Core's monitoring data
+    Monitor {
+        /// Keep polling at this interval in seconds
+        #[arg(long)]
+        watch: Option<u64>,
+        /// Show the latest agent loop instead of service health
+        #[arg(long)]
+        agent: bool,
+    },
+    /// Print the local log entries
+    Logs {
+        /// Only print the most recent entries
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Delete the log instead of printing it
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Stream microphone audio to Lily-Core and print transcriptions
+    Record {
+        /// Input device name; defaults to the saved microphone
+        #[arg(long)]
+        device: Option<String>,
+        /// Stream a WAV file instead of a microphone
+        #[arg(long, conflicts_with = "device")]
+        wav: Option<PathBuf>,
+        /// Stop after this many seconds instead of waiting for Ctrl-C
+        #[arg(long)]
+        seconds: Option<u64>,
+    },
+    /// Send a raw text frame over the WebSocket and print what comes back
+    WsSend {
+        message: String,
+        /// Seconds to keep listening for replies
+        #[arg(long, default_value_t = 5)]
+        wait: u64,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::init();
+    let cli = Cli::parse();
+    let output = Output { json: cli.json };
+
+    match run(&cli, &output).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            output.error(&e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: &Cli, output: &Output) -> Result<(), LilyError> {
+    let storage = SqliteStorage::new(StoragePaths::resolve(cli.data_dir.clone(), cli.portable)?);
+    let settings = storage.load_settings()?;
+    let server = server_settings(cli, &settings);
+    let lily_core = LilyCoreClient::new().with_server_settings(server.clone());
+
+    match &cli.command {
+        Command::Chat { message, tts, stream } => {
+            let tts_params = tts.then(|| settings.tts_params.clone());
+            let request = ChatRequest::new(message.clone(), tts_params);
+            if *stream {
+                return stream_chat(lily_core, request, output).await;
+            }
+            let reply = lily_core.send_chat(&request).await?;
+            output.value(&reply, || reply.response.clone());
+        }
+        Command::History { local } => {
+            let messages = if *local {
+                storage.load_chat_history()?
+            } else {
+                lily_core.get_conversation().await?
+            };
+            output.value(&messages, || format_messages(&messages));
+        }
+        Command::Clear { local } => {
+            if *local {
+                storage.clear_chat_history()?;
+            } else {
+                lily_core.clear_conversation().await?;
+            }
+            output.value(&serde_json::json!({ "cleared": true }), || "Conversation cleared".to_string());
+        }
+        Command::Monitor { watch, agent } => loop {
+            if *agent {
+                let agent_loop = lily_core.get_agent_loop().await?;
+                output.value(&agent_loop, || format_agent_loop(&agent_loop));
+            } else {
+                let snapshot = lily_core.get_monitoring().await?;
+                output.value(&snapshot, || format_monitoring(&snapshot));
+            }
+            let Some(seconds) = watch else { break };
+            tokio::time::sleep(Duration::from_secs(*seconds)).await;
+        },
+        Command::Logs { limit, clear } => {
+            if *clear {
+                storage.clear_logs()?;
+                output.value(&serde_json::json!({ "cleared": true }), || "Logs cleared".to_string());
+            } else {
+                let logs = match limit {
+                    Some(limit) => storage.recent_logs(*limit)?,
+                    None => storage.get_logs()?,
+                };
+                output.value(&logs, || format_logs(&logs));
+            }
+        }
+        Command::Record { device, wav, seconds } => {
+            record(server, &settings, device.clone(), wav.clone(), *seconds, output).await?;
+        }
+        Command::WsSend { message, wait } => {
+            let (websocket, mut events) = connect(server).await?;
+            websocket.send_message(message.clone()).await?;
+            print_events_until(&mut events, Instant::now() + Duration::from_secs(*wait), output).await;
+            websocket.disconnect().await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn server_settings(cli: &Cli, settings: &AppSettings) -> ServerSettings {
+    let mut server = settings.server.clone();
+    if let Some(url) = &cli.ws_url {
+        server.websocket_url = url.clone();
+    }
+    if let Some(url) = &cli.http_url {
+        server.http_base_url = url.clone();
+    }
+    // A one-shot tool should fail fast rather than retry forever
+    server.reconnect.max_attempts = Some(0);
+    server
+}
+
+// Connects and waits for Lily-Core to confirm registration
+async fn connect(server: ServerSettings) -> Result<(WebSocketService, UnboundedReceiver<EmittedEvent>), LilyError> {
+    let (sink, mut events) = ChannelEventSink::new();
+    let websocket = WebSocketService::new(Arc::new(sink)).with_server_settings(server);
+    websocket.connect().await?;
+
+    let registered = timeout(Duration::from_secs(10), async {
+        while let Some(event) = events.recv().await {
+            match event.name.as_str() {
+                "registration" => return true,
+                // Reconnects are disabled, so a failed dial ends here
+                "websocket-status" if event.payload["state"]["kind"] == "stopped" => return false,
+                _ => {}
+            }
+        }
+        false
+    })
+    .await;
+
+    if !matches!(registered, Ok(true)) {
+        let _ = websocket.disconnect().await;
+        return Err(LilyError::network("Could not register with Lily-Core"));
+    }
+    Ok((websocket, events))
+}
+
+async fn record(
+    server: ServerSettings,
+    settings: &AppSettings,
+    device: Option<String>,
+    wav: Option<PathBuf>,
+    seconds: Option<u64>,
+    output: &Output,
+) -> Result<(), LilyError> {
+    let audio_service = match wav {
+        Some(path) => AudioService::with_source(Arc::new(WavFileSource::open(&path).map_err(LilyError::audio)?)),
+        None => AudioService::new(),
+    };
+    audio_service.set_stream_settings(settings.audio.stream);
+    audio_service.set_vad_settings(settings.audio.vad);
+
+    let (websocket, mut events) = connect(server).await?;
+    audio_service.set_frame_output(Arc::new(websocket.clone()));
+
+    let handshake = ClientMessage::AudioStart(settings.audio.stream.format());
+    websocket.send_message(handshake.to_text()).await?;
+
+    let selection = audio_service.start_recording_with_device(device.or(settings.audio.input_device.clone())).await?;
+    output.value(&selection, || format!("Recording from '{}' (Ctrl-C to stop)", selection.device));
+
+    let stop_after = async {
+        match seconds {
+            Some(seconds) => tokio::time::sleep(Duration::from_secs(seconds)).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = print_events(&mut events, output) => {}
+        _ = stop_after => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    audio_service.stop_recording().await?;
+    websocket.disconnect().await
+}
+
+async fn stream_chat(lily_core: LilyCoreClient, request: ChatRequest, output: &Output) -> Result<(), LilyError> {
+    let (sink, mut events) = ChannelEventSink::new();
+    let chat = ChatService::new(lily_core, Arc::new(sink));
+    let message_id = chat.stream(request);
+
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = tokio::signal::ctrl_c() => {
+                chat.cancel(&message_id);
+                continue;
+            }
+        };
+        let Some(event) = event else {
+            return Ok(());
+        };
+        match event.name.as_str() {
+            "chat-error" => {
+                return Err(serde_json::from_value(event.payload["error"].clone())
+                    .unwrap_or_else(|_| LilyError::protocol(event.payload["error"].to_string())));
+            }
+            "chat-complete" | "chat-cancelled" => {
+                output.event(&event);
+                return Ok(());
+            }
+            _ => output.event(&event),
+        }
+    }
+}
+
+async fn print_events(events: &mut UnboundedReceiver<EmittedEvent>, output: &Output) {
+    while let Some(event) = events.recv().await {
+        output.event(&event);
+    }
+}
+
+async fn print_events_until(events: &mut UnboundedReceiver<EmittedEvent>, deadline: Instant, output: &Output) {
+    let _ = tokio::time::timeout_at(deadline, print_events(events, output)).await;
+}
+
+fn format_messages(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| format!("[{}] {}: {}", message.timestamp, message.role, message.content))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_monitoring(snapshot: &MonitoringSnapshot) -> String {
+    let mut lines = vec![format!("{} {} - {} ({})", snapshot.service_name, snapshot.version, snapshot.status, snapshot.timestamp)];
+    if let Some(metrics) = &snapshot.metrics {
+        let percent = |value: Option<f64>| value.map(|v| format!("{:.1}%", v)).unwrap_or_else(|| "-".to_string());
+        lines.push(format!(
+            "cpu {}  memory {}  disk {}  uptime {}",
+            percent(metrics.cpu_usage),
+            percent(metrics.memory_usage),
+            percent(metrics.disk_usage),
+            metrics.uptime.as_deref().unwrap_or("-")
+        ));
+    }
+    lines.extend(snapshot.services.iter().map(|service| format!("  {:<20} {}", service.name, service.status)));
+    lines.join("\n")
+}
+
+fn format_agent_loop(agent_loop: &AgentLoop) -> String {
+    if !agent_loop.exists {
+        return agent_loop.message.clone().unwrap_or_else(|| "No agent loop yet".to_string());
+    }
+    let mut lines = vec![format!("> {}", agent_loop.user_message)];
+    lines.extend(agent_loop.steps.iter().map(|step| {
+        format!("  {}. {} {}", step.step_number, step.kind, step.tool_name.as_deref().unwrap_or_default())
+    }));
+    if agent_loop.completed {
+        lines.push(format!("< {}", agent_loop.final_response));
+    }
+    lines.join("\n")
+}
+
+fn format_logs(logs: &[LogEntry]) -> String {
+    logs.iter()
+        .map(|entry| format!("{} {:<8} {}", entry.timestamp.format("%Y-%m-%d %H:%M:%S"), entry.type_, entry.message))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    fn value<T: Serialize>(&self, value: &T, text: impl FnOnce() -> String) {
+        if self.json {
+            println!("{}", serde_json::to_string(value).unwrap_or_default());
+        } else {
+            println!("{}", text());
+        }
+    }
+
+    fn event(&self, event: &EmittedEvent) {
+        if self.json {
+            println!("{}", serde_json::json!({ "event": event.name, "payload": event.payload }));
+            return;
+        }
+        match event.name.as_str() {
+            // Level updates arrive per audio callback; too noisy for a terminal
+            "audio-level" | "websocket-status" => {}
+            "transcription" => println!(
+                "[{}] {}",
+                event.payload["type"].as_str().unwrap_or("?"),
+                event.payload["text"].as_str().unwrap_or_default()
+            ),
+            "websocket-message" => println!("{}", event.payload.as_str().unwrap_or_default()),
+            "chat-delta" => {
+                print!("{}", event.payload["delta"].as_str().unwrap_or_default());
+                let _ = std::io::stdout().flush();
+            }
+            "chat-complete" => println!(),
+            "chat-cancelled" => println!("\n(cancelled)"),
+            _ => println!("{}: {}", event.name, event.payload),
+        }
+    }
+
+    fn error(&self, error: &LilyError) {
+        if self.json {
+            eprintln!("{}", serde_json::json!({ "error": error }));
+        } else {
+            eprintln!("error: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_global_flags_and_subcommands_parse() {
+        let cli = Cli::try_parse_from(["lily-cli", "record", "--device", "USB Mic", "--json", "--seconds", "5"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(cli.command, Command::Record { device: Some(ref name), seconds: Some(5), .. } if name == "USB Mic"));
+
+        let cli = Cli::try_parse_from(["lily-cli", "ws-send", "ping"]).unwrap();
+        assert!(matches!(cli.command, Command::WsSend { ref message, wait: 5 } if message == "ping"));
+
+        assert!(Cli::try_parse_from(["lily-cli", "record", "--device", "a", "--wav", "b.wav"]).is_err());
+
+        let cli = Cli::try_parse_from(["lily-cli", "logs", "--data-dir", "/tmp/qa"]).unwrap();
+        assert_eq!(cli.data_dir, Some(PathBuf::from("/tmp/qa")));
+        assert!(Cli::try_parse_from(["lily-cli", "logs", "--data-dir", "/tmp/qa", "--portable"]).is_err());
+    }
+
+    #[test]
+    fn test_url_overrides_apply_and_disable_reconnect() {
+        let cli = Cli::try_parse_from(["lily-cli", "--ws-url", "ws://core.lan:9100", "history"]).unwrap();
+        let server = server_settings(&cli, &AppSettings::default());
+        assert_eq!(server.websocket_url, "ws://core.lan:9100");
+        assert_eq!(server.http_base_url, ServerSettings::default().http_base_url);
+        assert_eq!(server.reconnect.max_attempts, Some(0));
+    }
+}
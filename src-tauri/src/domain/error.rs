@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What went wrong, as the `kind` tag of a serialized `LilyError`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Lily-Core could not be reached or the connection dropped.
+    Network,
+    /// Lily-Core answered with a non-success status.
+    Http { status: u16 },
+    /// A message or response did not have the expected shape.
+    Protocol,
+    /// Settings, history or logs could not be read or written.
+    Storage,
+    /// No usable audio device, or the audio backend failed.
+    Audio,
+    /// The WebSocket is not connected.
+    NotConnected,
+    /// The request itself was invalid for the current state.
+    Validation,
+    Timeout,
+    /// The user cancelled the request before it finished.
+    Cancelled,
+}
+
+/// Error returned by every command. Serializes as
+/// `{ "kind": ..., "status"?: ..., "message": ..., "details"?: ... }`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LilyError {
+    #[serde(flatten)]
+    pub kind: ErrorKind,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl LilyError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn network(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Network, message)
+    }
+
+    pub fn http(status: u16, message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Http { status }, message)
+    }
+
+    pub fn protocol(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Protocol, message)
+    }
+
+    pub fn storage(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Storage, message)
+    }
+
+    pub fn audio(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Audio, message)
+    }
+
+    pub fn not_connected(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotConnected, message)
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Validation, message)
+    }
+
+    pub fn timeout(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Timeout, message)
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Cancelled, message)
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+impl fmt::Display for LilyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Http { status } => write!(f, "HTTP error! status: {} {}", status, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for LilyError {}
+
+impl From<reqwest::Error> for LilyError {
+    fn from(e: reqwest::Error) -> Self {
+        let error = if e.is_timeout() {
+            LilyError::timeout(format!("Lily-Core timed out: {}", e))
+        } else if e.is_decode() {
+            LilyError::protocol(format!("Failed to parse response: {}", e))
+        } else {
+            LilyError::network(format!("Lily-Core unreachable: {}", e))
+        };
+        match e.url() {
+            Some(url) => error.with_details(serde_json::json!({ "url": url.as_str() })),
+            None => error,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for LilyError {
+    fn from(e: rusqlite::Error) -> Self {
+        LilyError::storage(format!("Database error: {}", e))
+    }
+}
+
+// Lets code that still reports plain strings use `?` on these errors
+impl From<LilyError> for String {
+    fn from(e: LilyError) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_serialize_to_a_stable_shape() {
+        let json = serde_json::to_value(LilyError::http(503, "agent busy")).unwrap();
+        assert_eq!(json, serde_json::json!({ "kind": "http", "status": 503, "message": "agent busy" }));
+
+        let json = serde_json::to_value(LilyError::not_connected("WebSocket not connected")).unwrap();
+        assert_eq!(json, serde_json::json!({ "kind": "not_connected", "message": "WebSocket not connected" }));
+
+        let error = LilyError::storage("Failed to parse settings").with_details(serde_json::json!({ "path": "settings.json" }));
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["details"]["path"], "settings.json");
+        assert_eq!(serde_json::from_value::<LilyError>(json).unwrap(), error);
+    }
+
+    #[test]
+    fn test_display_uses_message() {
+        assert_eq!(LilyError::audio("No input device").to_string(), "No input device");
+        assert_eq!(LilyError::http(404, "Not Found").to_string(), "HTTP error! status: 404 Not Found");
+    }
+}
@@ -1,22 +1,30 @@
-use crate::domain::models::{AppSettings, ChatMessage, LogEntry};
+use crate::domain::error::LilyError;
+use crate::domain::models::{AppSettings, ChatMessage, LogEntry, StorageRecovered};
 use serde_json;
-use tauri::AppHandle;
 use std::future::Future;
 
-pub trait FileStorageTrait {
-    fn save_settings(settings: AppSettings) -> Result<(), String>;
-    fn load_settings() -> Result<AppSettings, String>;
-    fn save_chat_history(messages: Vec<ChatMessage>) -> Result<(), String>;
-    fn load_chat_history() -> Result<Vec<ChatMessage>, String>;
-    fn clear_chat_history() -> Result<(), String>;
-    fn add_log_entry(type_: String, message: String, details: Option<serde_json::Value>) -> Result<(), String>;
-    fn get_logs() -> Result<Vec<LogEntry>, String>;
-    fn clear_logs() -> Result<(), String>;
+/// Where settings, chat history and logs are kept. Shared between services
+/// as `Arc<dyn FileStorageTrait>`.
+#[cfg_attr(test, mockall::automock)]
+pub trait FileStorageTrait: Send + Sync {
+    fn save_settings(&self, settings: AppSettings) -> Result<(), LilyError>;
+    fn load_settings(&self) -> Result<AppSettings, LilyError>;
+    fn save_chat_history(&self, messages: Vec<ChatMessage>) -> Result<(), LilyError>;
+    fn load_chat_history(&self) -> Result<Vec<ChatMessage>, LilyError>;
+    fn clear_chat_history(&self) -> Result<(), LilyError>;
+    fn add_log_entry(&self, type_: String, message: String, details: Option<serde_json::Value>) -> Result<(), LilyError>;
+    fn get_logs(&self) -> Result<Vec<LogEntry>, LilyError>;
+    fn clear_logs(&self) -> Result<(), LilyError>;
+
+    /// Files restored from a backup since the last call, oldest first.
+    fn take_recoveries(&self) -> Vec<StorageRecovered> {
+        Vec::new()
+    }
 }
 
 pub trait WebSocketTrait {
-    fn connect(app_handle: AppHandle) -> impl Future<Output = Result<(), String>> + Send;
-    fn disconnect(app_handle: AppHandle) -> impl Future<Output = Result<(), String>> + Send;
-    fn send_message(message: String, app_handle: AppHandle) -> impl Future<Output = Result<(), String>> + Send;
-    fn send_binary_data(data: Vec<u8>, app_handle: AppHandle) -> impl Future<Output = Result<(), String>> + Send;
+    fn connect(&self) -> impl Future<Output = Result<(), LilyError>> + Send;
+    fn disconnect(&self) -> impl Future<Output = Result<(), LilyError>> + Send;
+    fn send_message(&self, message: String) -> impl Future<Output = Result<(), LilyError>> + Send;
+    fn send_binary_data(&self, data: Vec<u8>) -> impl Future<Output = Result<(), LilyError>> + Send;
 }
\ No newline at end of file
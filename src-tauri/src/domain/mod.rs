@@ -1,2 +1,3 @@
+pub mod error;
 pub mod models;
 pub mod interfaces;
\ No newline at end of file
@@ -1,18 +1,55 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use crate::services::audio_pipeline::StreamSettings;
+use crate::services::barge_in::BargeInSettings;
+use crate::services::echo::EchoSettings;
+use crate::services::vad::VadSettings;
+use crate::services::audio_playback_service::AudioPlaybackService;
 use crate::services::audio_service::AudioService;
+use crate::services::chat::ChatService;
+use crate::domain::interfaces::FileStorageTrait;
+use crate::infrastructure::lily_core::LilyCoreClient;
+use crate::infrastructure::websocket::WebSocketService;
+use crate::services::events::EventSink;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+// Lifecycle of the Lily-Core connection, driven by `WebSocketService::websocket_handler`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "kind", content = "attempt", rename_all = "snake_case")]
+pub enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected,
+    Registering,
+    Registered,
+    Backoff(u32),
+    Stopped,
+}
+
+impl ConnectionState {
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectionState::Connected | ConnectionState::Registering | ConnectionState::Registered)
+    }
 
+    pub fn is_registered(&self) -> bool {
+        matches!(self, ConnectionState::Registered)
+    }
+}
+
+// Payload of the `websocket-status` event and `get_websocket_status` command
 #[derive(Serialize, Deserialize, Clone)]
 pub struct WebSocketStatus {
     pub connected: bool,
     pub registered: bool,
+    pub state: ConnectionState,
+    pub attempt: u32,
+    pub next_retry_at: Option<DateTime<Utc>>,
 }
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::net::TcpStream;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
-
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct TTSParameters {
     pub speaker: i32,
     pub sample_rate: i32,
@@ -20,17 +57,145 @@ pub struct TTSParameters {
     pub lang: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+impl Default for TTSParameters {
+    fn default() -> Self {
+        Self {
+            speaker: 0,
+            sample_rate: 24000,
+            model: "edge".to_string(),
+            lang: "en-US".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
     pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<MessageStatus>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Marks a message that does not hold a normal reply.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageStatus {
+    /// Stands in for a reply the user cancelled.
+    Cancelled,
+}
+
+// Lily-Core endpoints, re-read on every (re)connect and HTTP call
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerSettings {
+    pub websocket_url: String,
+    pub http_base_url: String,
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    #[serde(default)]
+    pub reconnect: ReconnectSettings,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            websocket_url: "ws://127.0.0.1:9002".to_string(),
+            http_base_url: "http://localhost:8000".to_string(),
+            path_prefix: None,
+            reconnect: ReconnectSettings::default(),
+        }
+    }
+}
+
+// Exponential backoff between WebSocket reconnect attempts
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ReconnectSettings {
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,
+    /// Fraction of the delay randomised in either direction, from 0.0 to 1.0.
+    pub jitter: f64,
+    /// Give up and enter `ConnectionState::Stopped` after this many failed attempts.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectSettings {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectSettings {
+    /// Delay before retry number `attempt` (1-based). `unit` is a uniform sample
+    /// in `[0, 1)` used for jitter, so the curve can be tested deterministically.
+    pub fn delay_for(&self, attempt: u32, unit: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(63) as i32;
+        let max = self.max_delay_ms as f64;
+        let base = (self.initial_delay_ms as f64 * self.multiplier.max(1.0).powi(exponent)).min(max);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 - jitter + 2.0 * jitter * unit.clamp(0.0, 1.0);
+
+        Duration::from_millis((base * factor).min(max).round() as u64)
+    }
+}
+
+impl ServerSettings {
+    /// Builds a full HTTP URL for `path`, inserting the optional path prefix
+    /// between the base URL and the route (e.g. `http://host:8000/api/chat`).
+    pub fn http_url(&self, path: &str) -> String {
+        let base = self.http_base_url.trim_end_matches('/');
+        let prefix = self
+            .path_prefix
+            .as_deref()
+            .map(|p| p.trim_matches('/'))
+            .filter(|p| !p.is_empty());
+        let path = path.trim_start_matches('/');
+
+        match prefix {
+            Some(prefix) => format!("{}/{}/{}", base, prefix, path),
+            None => format!("{}/{}", base, path),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AudioSettings {
+    /// Preferred microphone by cpal device name; `None` uses the system default.
+    #[serde(default)]
+    pub input_device: Option<String>,
+    /// Speaker used for native TTS playback; `None` uses the system default.
+    #[serde(default)]
+    pub output_device: Option<String>,
+    /// Format microphone audio is converted to before streaming to Lily-Core.
+    #[serde(default)]
+    pub stream: StreamSettings,
+    /// Voice activity detection run on the captured stream.
+    #[serde(default)]
+    pub vad: VadSettings,
+    /// What happens to TTS playback when the user starts talking over it.
+    #[serde(default)]
+    pub barge_in: BargeInSettings,
+    /// Suppression of TTS playback picked up by the microphone.
+    #[serde(default)]
+    pub echo: EchoSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct AppSettings {
     pub tts_params: TTSParameters,
     pub tts_enabled: bool,
+    #[serde(default)]
+    pub server: ServerSettings,
+    #[serde(default)]
+    pub audio: AudioSettings,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -42,27 +207,131 @@ pub struct LogEntry {
     pub details: Option<serde_json::Value>,
 }
 
-// WebSocket state
+/// Payload of the `storage-recovered` event: `file` could not be read and was
+/// restored from its backup. The damaged copy is kept next to it as `.corrupt`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StorageRecovered {
+    pub file: String,
+    pub backup: String,
+    pub error: String,
+}
+
+// WebSocket state. The socket is split: the reader owns the `WsSource`, and
+// the writer task owns the `WsSink` and drains `outbound` in order.
 pub struct WebSocketState {
-    pub stream: Option<Arc<Mutex<WebSocketStream<MaybeTlsStream<TcpStream>>>>>,
-    pub is_connected: bool,
-    pub is_registered: bool,
-    pub app_handle: Option<tauri::AppHandle>,
+    pub outbound: Option<mpsc::Sender<Message>>,
+    pub connection: ConnectionState,
+    pub attempt: u32,
+    pub next_retry_at: Option<DateTime<Utc>>,
+    pub handler_task: Option<JoinHandle<()>>,
+}
+
+impl Default for WebSocketState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WebSocketState {
     pub fn new() -> Self {
         Self {
-            stream: None,
-            is_connected: false,
-            is_registered: false,
-            app_handle: None,
+            outbound: None,
+            connection: ConnectionState::Disconnected,
+            attempt: 0,
+            next_retry_at: None,
+            handler_task: None,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_connected()
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.connection.is_registered()
+    }
+
+    pub fn status(&self) -> WebSocketStatus {
+        WebSocketStatus {
+            connected: self.is_connected(),
+            registered: self.is_registered(),
+            state: self.connection,
+            attempt: self.attempt,
+            next_retry_at: self.next_retry_at,
         }
     }
 }
 
 // Global state for WebSocket and Audio
 pub struct AppState {
-    pub ws_state: Arc<Mutex<WebSocketState>>,
+    pub storage: Arc<dyn FileStorageTrait>,
+    pub websocket: WebSocketService,
+    pub lily_core: LilyCoreClient,
+    pub chat: ChatService,
+    pub events: Arc<dyn EventSink>,
     pub audio_service: Arc<AudioService>,
-}
\ No newline at end of file
+    pub playback_service: Arc<AudioPlaybackService>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_settings_defaults() {
+        let server = ServerSettings::default();
+        assert_eq!(server.websocket_url, "ws://127.0.0.1:9002");
+        assert_eq!(server.http_url("/chat"), "http://localhost:8000/chat");
+    }
+
+    #[test]
+    fn test_server_settings_http_url_with_prefix() {
+        let server = ServerSettings {
+            websocket_url: "ws://core.lan:9100".to_string(),
+            http_base_url: "http://core.lan:8100/".to_string(),
+            path_prefix: Some("/lily/".to_string()),
+            ..ServerSettings::default()
+        };
+        assert_eq!(server.http_url("/conversation/default_user"), "http://core.lan:8100/lily/conversation/default_user");
+        assert_eq!(server.http_url("monitoring"), "http://core.lan:8100/lily/monitoring");
+    }
+
+    #[test]
+    fn test_reconnect_delay_grows_and_caps() {
+        let reconnect = ReconnectSettings { jitter: 0.0, ..ReconnectSettings::default() };
+        assert_eq!(reconnect.delay_for(1, 0.5), Duration::from_millis(500));
+        assert_eq!(reconnect.delay_for(2, 0.5), Duration::from_millis(1000));
+        assert_eq!(reconnect.delay_for(4, 0.5), Duration::from_millis(4000));
+        assert_eq!(reconnect.delay_for(20, 0.5), Duration::from_millis(30_000));
+        assert_eq!(reconnect.delay_for(u32::MAX, 0.5), Duration::from_millis(30_000));
+    }
+
+    #[test]
+    fn test_reconnect_delay_jitter_bounds() {
+        let reconnect = ReconnectSettings::default();
+        assert_eq!(reconnect.delay_for(3, 0.0), Duration::from_millis(1600));
+        assert_eq!(reconnect.delay_for(3, 0.5), Duration::from_millis(2000));
+        assert!(reconnect.delay_for(3, 0.999) <= Duration::from_millis(2400));
+        // Jitter never pushes past the configured ceiling
+        assert_eq!(reconnect.delay_for(30, 0.999), Duration::from_millis(30_000));
+    }
+
+    #[test]
+    fn test_connection_state_serialization() {
+        let json = serde_json::to_value(ConnectionState::Backoff(3)).unwrap();
+        assert_eq!(json, serde_json::json!({ "kind": "backoff", "attempt": 3 }));
+        let json = serde_json::to_value(ConnectionState::Registered).unwrap();
+        assert_eq!(json, serde_json::json!({ "kind": "registered" }));
+        assert!(ConnectionState::Registering.is_connected());
+        assert!(!ConnectionState::Backoff(1).is_connected());
+    }
+
+    #[test]
+    fn test_settings_without_server_section_use_defaults() {
+        let json = r#"{"tts_params":{"speaker":1,"sample_rate":24000,"model":"edge","lang":"en-US"},"tts_enabled":true}"#;
+        let settings: AppSettings = serde_json::from_str(json).unwrap();
+        assert!(settings.tts_enabled);
+        assert_eq!(settings.server, ServerSettings::default());
+        assert_eq!(settings.audio.input_device, None);
+    }
+}
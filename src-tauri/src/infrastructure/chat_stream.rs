@@ -0,0 +1,331 @@
+use crate::domain::error::LilyError;
+use crate::infrastructure::lily_core::ChatResponse;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Lily-Core streams `/chat/stream` replies as server-sent events, newline-
+// delimited JSON or bare chunked text. JSON payloads look like
+// `{"delta":"tok"}`, `{"done":true,"response":"...","timestamp":"..."}` or
+// `{"error":"..."}`; SSE may also signal the end with `event: done` or
+// `data: [DONE]`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatStreamEvent {
+    Delta(String),
+    /// The reply is complete; `response` is the server's or the joined deltas.
+    Done(ChatResponse),
+    /// Lily-Core reported a failure mid-stream.
+    Error(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamFraming {
+    Sse,
+    Ndjson,
+    Text,
+}
+
+impl StreamFraming {
+    pub fn from_content_type(content_type: &str) -> Self {
+        let content_type = content_type.to_ascii_lowercase();
+        if content_type.starts_with("text/event-stream") {
+            StreamFraming::Sse
+        } else if content_type.contains("ndjson") || content_type.contains("jsonl") {
+            StreamFraming::Ndjson
+        } else {
+            StreamFraming::Text
+        }
+    }
+}
+
+/// Turns body chunks into stream events, whatever the chunk boundaries.
+pub struct ChatStreamDecoder {
+    framing: StreamFraming,
+    buffer: Vec<u8>,
+    // Pending SSE event
+    event: Option<String>,
+    data: Vec<String>,
+    text: String,
+    done: bool,
+}
+
+impl ChatStreamDecoder {
+    pub fn new(framing: StreamFraming) -> Self {
+        Self {
+            framing,
+            buffer: Vec::new(),
+            event: None,
+            data: Vec::new(),
+            text: String::new(),
+            done: false,
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<ChatStreamEvent> {
+        let mut events = Vec::new();
+        if self.done {
+            return events;
+        }
+
+        if self.framing == StreamFraming::Text {
+            self.buffer.extend_from_slice(chunk);
+            // Hold back an incomplete UTF-8 sequence until the next chunk
+            let valid = match std::str::from_utf8(&self.buffer) {
+                Ok(_) => self.buffer.len(),
+                Err(e) => e.valid_up_to(),
+            };
+            let delta = String::from_utf8_lossy(&self.buffer[..valid]).into_owned();
+            self.buffer.drain(..valid);
+            self.delta(delta, &mut events);
+            return events;
+        }
+
+        self.buffer.extend_from_slice(chunk);
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            self.line(line.trim_end_matches(['\n', '\r']), &mut events);
+            if self.done {
+                break;
+            }
+        }
+        events
+    }
+
+    /// Flushes anything buffered once the body ends. A stream that closes
+    /// without an explicit end marker is complete with what arrived.
+    pub fn finish(&mut self) -> Vec<ChatStreamEvent> {
+        let mut events = Vec::new();
+        if self.done {
+            return events;
+        }
+        if !self.buffer.is_empty() {
+            let rest = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+            match self.framing {
+                StreamFraming::Text => self.delta(rest, &mut events),
+                _ => self.line(rest.trim_end_matches('\r'), &mut events),
+            }
+        }
+        if self.framing == StreamFraming::Sse && !self.done {
+            self.line("", &mut events);
+        }
+        if !self.done {
+            self.complete(None, None, &mut events);
+        }
+        events
+    }
+
+    fn line(&mut self, line: &str, events: &mut Vec<ChatStreamEvent>) {
+        match self.framing {
+            StreamFraming::Ndjson => {
+                if !line.trim().is_empty() {
+                    self.payload(None, line, events);
+                }
+            }
+            StreamFraming::Sse => {
+                if line.is_empty() {
+                    // Blank line dispatches the pending event
+                    let event = self.event.take();
+                    if !self.data.is_empty() || event.is_some() {
+                        let data = std::mem::take(&mut self.data).join("\n");
+                        self.payload(event.as_deref(), &data, events);
+                    }
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    self.data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+                } else if let Some(value) = line.strip_prefix("event:") {
+                    self.event = Some(value.trim().to_string());
+                }
+                // Comments (`:`), `id:` and `retry:` are ignored
+            }
+            StreamFraming::Text => {}
+        }
+    }
+
+    fn payload(&mut self, event: Option<&str>, data: &str, events: &mut Vec<ChatStreamEvent>) {
+        if data.trim() == "[DONE]" {
+            self.complete(None, None, events);
+            return;
+        }
+
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(data) else {
+            match event {
+                Some("error") => self.fail(data.to_string(), events),
+                Some("done") => self.complete(None, None, events),
+                _ => self.delta(data.to_string(), events),
+            }
+            return;
+        };
+
+        let text = |key: &str| json.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        if event == Some("error") || json.get("error").is_some() {
+            let message = json.get("error")
+                .and_then(|e| e.as_str().map(str::to_string).or_else(|| e.get("message")?.as_str().map(str::to_string)))
+                .or_else(|| text("message"))
+                .unwrap_or_else(|| data.to_string());
+            self.fail(message, events);
+        } else if event == Some("done") || json.get("done").and_then(|v| v.as_bool()) == Some(true) {
+            self.complete(text("response"), text("timestamp"), events);
+        } else if let Some(delta) = text("delta").or_else(|| text("token")).or_else(|| text("content")) {
+            self.delta(delta, events);
+        }
+    }
+
+    fn delta(&mut self, delta: String, events: &mut Vec<ChatStreamEvent>) {
+        if !delta.is_empty() {
+            self.text.push_str(&delta);
+            events.push(ChatStreamEvent::Delta(delta));
+        }
+    }
+
+    fn complete(&mut self, response: Option<String>, timestamp: Option<String>, events: &mut Vec<ChatStreamEvent>) {
+        self.done = true;
+        events.push(ChatStreamEvent::Done(ChatResponse {
+            response: response.unwrap_or_else(|| self.text.clone()),
+            timestamp: timestamp.unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        }));
+    }
+
+    fn fail(&mut self, message: String, events: &mut Vec<ChatStreamEvent>) {
+        self.done = true;
+        events.push(ChatStreamEvent::Error(message));
+    }
+}
+
+/// A streaming reply being read from Lily-Core.
+pub struct ChatStream {
+    response: reqwest::Response,
+    decoder: ChatStreamDecoder,
+    pending: VecDeque<ChatStreamEvent>,
+    idle_timeout: Duration,
+    ended: bool,
+}
+
+impl ChatStream {
+    pub(crate) fn new(response: reqwest::Response, idle_timeout: Duration) -> Self {
+        let framing = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(StreamFraming::from_content_type)
+            .unwrap_or(StreamFraming::Text);
+        Self {
+            response,
+            decoder: ChatStreamDecoder::new(framing),
+            pending: VecDeque::new(),
+            idle_timeout,
+            ended: false,
+        }
+    }
+
+    /// Next event, or `None` once the reply has completed or failed.
+    pub async fn next(&mut self) -> Option<Result<ChatStreamEvent, LilyError>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                if !matches!(event, ChatStreamEvent::Delta(_)) {
+                    self.ended = true;
+                    self.pending.clear();
+                }
+                return Some(Ok(event));
+            }
+            if self.ended {
+                return None;
+            }
+
+            let chunk = match tokio::time::timeout(self.idle_timeout, self.response.chunk()).await {
+                Ok(Ok(chunk)) => chunk,
+                Ok(Err(e)) => {
+                    self.ended = true;
+                    return Some(Err(e.into()));
+                }
+                Err(_) => {
+                    self.ended = true;
+                    return Some(Err(LilyError::timeout(format!(
+                        "No data from Lily-Core for {} seconds",
+                        self.idle_timeout.as_secs()
+                    ))));
+                }
+            };
+
+            let events = match chunk {
+                Some(bytes) => self.decoder.push(&bytes),
+                None => self.decoder.finish(),
+            };
+            self.pending.extend(events);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(framing: StreamFraming, chunks: &[&str]) -> Vec<ChatStreamEvent> {
+        let mut decoder = ChatStreamDecoder::new(framing);
+        let mut events: Vec<_> = chunks.iter().flat_map(|chunk| decoder.push(chunk.as_bytes())).collect();
+        events.extend(decoder.finish());
+        events
+    }
+
+    fn deltas(events: &[ChatStreamEvent]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ChatStreamEvent::Delta(delta) => Some(delta.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sse_split_across_chunks() {
+        let events = decode(
+            StreamFraming::Sse,
+            &["data: {\"delta\":\"Hel\"}\n", "\ndata: {\"del", "ta\":\"lo\"}\n\n", "event: done\ndata: {\"response\":\"Hello\",\"timestamp\":\"t\"}\n\n"],
+        );
+        assert_eq!(deltas(&events), vec!["Hel", "lo"]);
+        assert_eq!(events.last(), Some(&ChatStreamEvent::Done(ChatResponse { response: "Hello".to_string(), timestamp: "t".to_string() })));
+    }
+
+    #[test]
+    fn test_sse_done_marker_joins_deltas() {
+        let events = decode(StreamFraming::Sse, &[": keep-alive\n\ndata: {\"token\":\"a\"}\r\n\r\ndata: {\"token\":\"b\"}\n\ndata: [DONE]\n\n"]);
+        match events.last() {
+            Some(ChatStreamEvent::Done(response)) => assert_eq!(response.response, "ab"),
+            other => panic!("expected done, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_errors_end_the_stream() {
+        let events = decode(StreamFraming::Sse, &["data: {\"delta\":\"x\"}\n\nevent: error\ndata: {\"message\":\"agent crashed\"}\n\ndata: {\"delta\":\"y\"}\n\n"]);
+        assert_eq!(events, vec![ChatStreamEvent::Delta("x".to_string()), ChatStreamEvent::Error("agent crashed".to_string())]);
+
+        let events = decode(StreamFraming::Ndjson, &["{\"error\":\"timeout in tool\"}\n"]);
+        assert_eq!(events, vec![ChatStreamEvent::Error("timeout in tool".to_string())]);
+    }
+
+    #[test]
+    fn test_ndjson_without_trailing_newline() {
+        let events = decode(StreamFraming::Ndjson, &["{\"delta\":\"one \"}\n{\"del", "ta\":\"two\"}"]);
+        assert_eq!(deltas(&events), vec!["one ", "two"]);
+        assert!(matches!(events.last(), Some(ChatStreamEvent::Done(response)) if response.response == "one two"));
+    }
+
+    #[test]
+    fn test_plain_text_keeps_split_utf8_together() {
+        let bytes = "héllo".as_bytes();
+        let mut decoder = ChatStreamDecoder::new(StreamFraming::Text);
+        let mut events = decoder.push(&bytes[..2]);
+        events.extend(decoder.push(&bytes[2..]));
+        events.extend(decoder.finish());
+        assert_eq!(deltas(&events).concat(), "héllo");
+        assert!(matches!(events.last(), Some(ChatStreamEvent::Done(response)) if response.response == "héllo"));
+    }
+
+    #[test]
+    fn test_framing_from_content_type() {
+        assert_eq!(StreamFraming::from_content_type("text/event-stream; charset=utf-8"), StreamFraming::Sse);
+        assert_eq!(StreamFraming::from_content_type("application/x-ndjson"), StreamFraming::Ndjson);
+        assert_eq!(StreamFraming::from_content_type("text/plain"), StreamFraming::Text);
+    }
+}
@@ -1,153 +1,115 @@
+use crate::domain::error::LilyError;
 use crate::domain::interfaces::FileStorageTrait;
-use crate::domain::models::{AppSettings, ChatMessage, LogEntry, TTSParameters};
+use crate::domain::models::{AppSettings, ChatMessage, LogEntry, StorageRecovered};
+use crate::infrastructure::storage_paths::StoragePaths;
+use crate::services::events::EventSink;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json;
-use std::fs;
+use log::warn;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 use chrono::Utc;
 
-pub struct FileStorage;
+struct RecoveryReports {
+    events: Option<Arc<dyn EventSink>>,
+    pending: Vec<StorageRecovered>,
+}
 
-impl FileStorageTrait for FileStorage {
-    fn save_settings(settings: AppSettings) -> Result<(), String> {
-        // Get the app data directory
-        let app_data_dir = dirs::data_dir()
-            .ok_or("Could not determine app data directory")?
-            .join("NsTut")
-            .join("LilyUI");
-        
-        // Create directories if they don't exist
-        fs::create_dir_all(&app_data_dir)
-            .map_err(|e| format!("Failed to create directories: {}", e))?;
-        
-        // Write settings to file
-        let settings_path = app_data_dir.join("settings.json");
-        let json = serde_json::to_string_pretty(&settings)
-            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-        
-        fs::write(&settings_path, json)
-            .map_err(|e| format!("Failed to write settings file: {}", e))?;
-        
-        Ok(())
+/// Settings, chat history and logs as JSON files under one data directory.
+pub struct FileStorage {
+    paths: StoragePaths,
+    // Recoveries usually happen at startup, before anything listens, so they
+    // are also kept until the UI takes them
+    recoveries: Mutex<RecoveryReports>,
+}
+
+impl FileStorage {
+    pub fn new(paths: StoragePaths) -> Self {
+        Self {
+            paths,
+            recoveries: Mutex::new(RecoveryReports { events: None, pending: Vec::new() }),
+        }
     }
 
-    fn load_settings() -> Result<AppSettings, String> {
-        // Get the app data directory
-        let app_data_dir = dirs::data_dir()
-            .ok_or("Could not determine app data directory")?
-            .join("NsTut")
-            .join("LilyUI");
-        
-        // Read settings from file
-        let settings_path = app_data_dir.join("settings.json");
-        
-        if !settings_path.exists() {
-            // Return default settings if file doesn't exist
-            return Ok(AppSettings {
-                tts_params: TTSParameters {
-                    speaker: 0,
-                    sample_rate: 24000,
-                    model: "edge".to_string(),
-                    lang: "en-US".to_string(),
-                },
-                tts_enabled: false,
-            });
+    /// Also emits `storage-recovered` through `events` when a file is restored.
+    pub fn with_event_sink(self, events: Arc<dyn EventSink>) -> Self {
+        self.recoveries.lock().unwrap_or_else(|e| e.into_inner()).events = Some(events);
+        self
+    }
+
+    fn report(&self, recovered: StorageRecovered) {
+        let mut reports = self.recoveries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(events) = &reports.events {
+            if let Err(e) = events.emit("storage-recovered", &recovered) {
+                warn!("{}", e);
+            }
         }
-        
-        let json = fs::read_to_string(&settings_path)
-            .map_err(|e| format!("Failed to read settings file: {}", e))?;
-        
-        let settings: AppSettings = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to parse settings: {}", e))?;
-        
-        Ok(settings)
-    }
-
-    fn save_chat_history(messages: Vec<ChatMessage>) -> Result<(), String> {
-        // Get the app data directory
-        let app_data_dir = dirs::data_dir()
-            .ok_or("Could not determine app data directory")?
-            .join("NsTut")
-            .join("LilyUI");
-        
-        // Create directories if they don't exist
-        fs::create_dir_all(&app_data_dir)
-            .map_err(|e| format!("Failed to create directories: {}", e))?;
-        
-        // Write chat history to file
-        let history_path = app_data_dir.join("chat_history.json");
-        let json = serde_json::to_string_pretty(&messages)
-            .map_err(|e| format!("Failed to serialize chat history: {}", e))?;
-        
-        fs::write(&history_path, json)
-            .map_err(|e| format!("Failed to write chat history file: {}", e))?;
-        
-        Ok(())
+        reports.pending.push(recovered);
+    }
+
+    fn save_json<T: Serialize>(&self, path: &Path, what: &str, value: &T) -> Result<(), LilyError> {
+        fs::create_dir_all(self.paths.root())
+            .map_err(|e| LilyError::storage(format!("Failed to create directories: {}", e)))?;
+
+        let json = serde_json::to_string_pretty(value)
+            .map_err(|e| LilyError::storage(format!("Failed to serialize {}: {}", what, e)))?;
+        write_atomic(path, json.as_bytes())
+            .map_err(|e| LilyError::storage(format!("Failed to write {} file: {}", what, e)))
     }
 
-    fn load_chat_history() -> Result<Vec<ChatMessage>, String> {
-        // Get the app data directory
-        let app_data_dir = dirs::data_dir()
-            .ok_or("Could not determine app data directory")?
-            .join("NsTut")
-            .join("LilyUI");
-        
-        // Read chat history from file
-        let history_path = app_data_dir.join("chat_history.json");
-        
-        if !history_path.exists() {
-            // Return empty vector if file doesn't exist
-            return Ok(Vec::new());
+    fn load_json<T: DeserializeOwned>(&self, path: &Path, what: &str) -> Result<Option<T>, LilyError> {
+        let (value, recovered) = read_json(path, what)?;
+        if let Some(recovered) = recovered {
+            self.report(recovered);
         }
-        
-        let json = fs::read_to_string(&history_path)
-            .map_err(|e| format!("Failed to read chat history file: {}", e))?;
-        
-        let messages: Vec<ChatMessage> = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to parse chat history: {}", e))?;
-        
-        Ok(messages)
-    }
-
-    fn clear_chat_history() -> Result<(), String> {
-        // Get the app data directory
-        let app_data_dir = dirs::data_dir()
-            .ok_or("Could not determine app data directory")?
-            .join("NsTut")
-            .join("LilyUI");
-        
-        // Remove chat history file
-        let history_path = app_data_dir.join("chat_history.json");
-        
-        if history_path.exists() {
-            fs::remove_file(&history_path)
-                .map_err(|e| format!("Failed to remove chat history file: {}", e))?;
+        Ok(value)
+    }
+
+    fn remove(path: PathBuf, what: &str) -> Result<(), LilyError> {
+        // The backup goes too, or the next load would bring the old data back
+        for path in [sibling(&path, "bak"), path] {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => {
+                    return Err(LilyError::storage(format!("Failed to remove {} file: {}", what, e)));
+                }
+                _ => {}
+            }
         }
-        
         Ok(())
     }
+}
+
+impl FileStorageTrait for FileStorage {
+    fn save_settings(&self, settings: AppSettings) -> Result<(), LilyError> {
+        self.save_json(&self.paths.settings_file(), "settings", &settings)
+    }
+
+    fn load_settings(&self) -> Result<AppSettings, LilyError> {
+        // Default settings if the file doesn't exist
+        Ok(self.load_json(&self.paths.settings_file(), "settings")?.unwrap_or_default())
+    }
+
+    fn save_chat_history(&self, messages: Vec<ChatMessage>) -> Result<(), LilyError> {
+        self.save_json(&self.paths.chat_history_file(), "chat history", &messages)
+    }
+
+    fn load_chat_history(&self) -> Result<Vec<ChatMessage>, LilyError> {
+        Ok(self.load_json(&self.paths.chat_history_file(), "chat history")?.unwrap_or_default())
+    }
+
+    fn clear_chat_history(&self) -> Result<(), LilyError> {
+        Self::remove(self.paths.chat_history_file(), "chat history")
+    }
 
-    fn add_log_entry(type_: String, message: String, details: Option<serde_json::Value>) -> Result<(), String> {
-        // Get the app data directory
-        let app_data_dir = dirs::data_dir()
-            .ok_or("Could not determine app data directory")?
-            .join("NsTut")
-            .join("LilyUI");
-        
-        // Create directories if they don't exist
-        fs::create_dir_all(&app_data_dir)
-            .map_err(|e| format!("Failed to create directories: {}", e))?;
-        
+    fn add_log_entry(&self, type_: String, message: String, details: Option<serde_json::Value>) -> Result<(), LilyError> {
         // Read existing logs
-        let logs_path = app_data_dir.join("logs.json");
-        let mut logs: Vec<LogEntry> = if logs_path.exists() {
-            let json = fs::read_to_string(&logs_path)
-                .map_err(|e| format!("Failed to read logs file: {}", e))?;
-            serde_json::from_str(&json)
-                .map_err(|e| format!("Failed to parse logs: {}", e))?
-        } else {
-            Vec::new()
-        };
-        
+        let mut logs: Vec<LogEntry> = self.load_json(&self.paths.logs_file(), "logs")?.unwrap_or_default();
+
         // Add new log entry
         let new_log = LogEntry {
             id: Uuid::new_v4().to_string(),
@@ -156,63 +118,172 @@ impl FileStorageTrait for FileStorage {
             message,
             details,
         };
-        
+
         logs.push(new_log);
-        
+
         // Keep only the last 1000 logs to prevent file from growing too large
         if logs.len() > 1000 {
             logs.drain(0..logs.len() - 1000);
         }
-        
-        // Write logs back to file
-        let json = serde_json::to_string_pretty(&logs)
-            .map_err(|e| format!("Failed to serialize logs: {}", e))?;
-        
-        fs::write(&logs_path, json)
-            .map_err(|e| format!("Failed to write logs file: {}", e))?;
-        
-        Ok(())
+
+        self.save_json(&self.paths.logs_file(), "logs", &logs)
     }
 
-    fn get_logs() -> Result<Vec<LogEntry>, String> {
-        // Get the app data directory
-        let app_data_dir = dirs::data_dir()
-            .ok_or("Could not determine app data directory")?
-            .join("NsTut")
-            .join("LilyUI");
-        
-        // Read logs from file
-        let logs_path = app_data_dir.join("logs.json");
-        
-        if !logs_path.exists() {
-            // Return empty vector if file doesn't exist
-            return Ok(Vec::new());
-        }
-        
-        let json = fs::read_to_string(&logs_path)
-            .map_err(|e| format!("Failed to read logs file: {}", e))?;
-        
-        let logs: Vec<LogEntry> = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to parse logs: {}", e))?;
-        
-        Ok(logs)
-    }
-
-    fn clear_logs() -> Result<(), String> {
-        // Get the app data directory
-        let app_data_dir = dirs::data_dir()
-            .ok_or("Could not determine app data directory")?
-            .join("NsTut")
-            .join("LilyUI");
-        
-        // Remove logs file
-        let logs_path = app_data_dir.join("logs.json");
-        
-        if logs_path.exists() {
-            fs::remove_file(&logs_path)
-                .map_err(|e| format!("Failed to remove logs file: {}", e))?;
+    fn get_logs(&self) -> Result<Vec<LogEntry>, LilyError> {
+        Ok(self.load_json(&self.paths.logs_file(), "logs")?.unwrap_or_default())
+    }
+
+    fn clear_logs(&self) -> Result<(), LilyError> {
+        Self::remove(self.paths.logs_file(), "logs")
+    }
+
+    fn take_recoveries(&self) -> Vec<StorageRecovered> {
+        std::mem::take(&mut self.recoveries.lock().unwrap_or_else(|e| e.into_inner()).pending)
+    }
+}
+
+// `settings.json` -> `settings.json.bak`
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+// Writes to a temporary file and flushes it to disk before renaming it over `path`
+fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp = sibling(path, "tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp, path)?;
+
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Replaces `path` so that a crash leaves either the old or the new contents,
+/// keeping the previous version as `<path>.bak`.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if path.exists() {
+        // A crash before the next rename leaves only the backup, which loads recover from
+        fs::rename(path, sibling(path, "bak"))?;
+    }
+    write_synced(path, contents)
+}
+
+/// Reads `path`, falling back to its `.bak` copy if it is missing or damaged.
+/// Returns `None` when neither exists, and the recovery if the backup was used.
+pub(crate) fn read_json<T: DeserializeOwned>(path: &Path, what: &str) -> Result<(Option<T>, Option<StorageRecovered>), LilyError> {
+    let backup = sibling(path, "bak");
+    let error = match fs::read_to_string(path) {
+        Ok(json) => match serde_json::from_str(&json) {
+            Ok(value) => return Ok((Some(value), None)),
+            Err(e) => format!("Failed to parse {}: {}", what, e),
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            if !backup.exists() {
+                return Ok((None, None));
+            }
+            format!("The {} file is missing", what)
         }
-        
-        Ok(())
+        Err(e) => return Err(LilyError::storage(format!("Failed to read {} file: {}", what, e))),
+    };
+
+    let recovered = fs::read_to_string(&backup)
+        .ok()
+        .and_then(|json| serde_json::from_str::<T>(&json).ok().map(|value| (json, value)));
+    let Some((json, value)) = recovered else {
+        return Err(LilyError::storage(error));
+    };
+
+    warn!("{}; restoring {} from {}", error, path.display(), backup.display());
+    if path.exists() {
+        fs::rename(path, sibling(path, "corrupt"))
+            .map_err(|e| LilyError::storage(format!("Failed to set aside damaged {} file: {}", what, e)))?;
+    }
+    write_synced(path, json.as_bytes())
+        .map_err(|e| LilyError::storage(format!("Failed to restore {} file: {}", what, e)))?;
+
+    let file = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    Ok((Some(value), Some(StorageRecovered { file, backup: backup.display().to_string(), error })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lily-files-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_atomic_write_keeps_previous_version() {
+        let dir = temp_dir();
+        let path = dir.join("settings.json");
+
+        write_atomic(&path, b"[1]").unwrap();
+        assert!(!sibling(&path, "bak").exists());
+        write_atomic(&path, b"[2]").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[2]");
+        assert_eq!(fs::read_to_string(sibling(&path, "bak")).unwrap(), "[1]");
+        assert!(!sibling(&path, "tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_damaged_file_is_restored_from_backup() {
+        let dir = temp_dir();
+        let path = dir.join("chat_history.json");
+        write_atomic(&path, b"[1, 2]").unwrap();
+        write_atomic(&path, b"[1, 2, 3]").unwrap();
+        // Torn write
+        fs::write(&path, b"[1, 2, 3").unwrap();
+
+        let (value, recovered) = read_json::<Vec<u8>>(&path, "chat history").unwrap();
+        assert_eq!(value, Some(vec![1, 2]));
+        let recovered = recovered.unwrap();
+        assert_eq!(recovered.file, "chat_history.json");
+        assert!(recovered.error.starts_with("Failed to parse chat history"));
+
+        assert_eq!(fs::read_to_string(sibling(&path, "corrupt")).unwrap(), "[1, 2, 3");
+        // Restored in place, so the next read needs no recovery
+        assert_eq!(read_json::<Vec<u8>>(&path, "chat history").unwrap(), (Some(vec![1, 2]), None));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_file_with_backup_is_recovered() {
+        let dir = temp_dir();
+        let path = dir.join("settings.json");
+        assert_eq!(read_json::<Vec<u8>>(&path, "settings").unwrap(), (None, None));
+
+        // Crash between moving the old file aside and renaming the new one in
+        fs::write(sibling(&path, "bak"), b"[7]").unwrap();
+        let (value, recovered) = read_json::<Vec<u8>>(&path, "settings").unwrap();
+        assert_eq!(value, Some(vec![7]));
+        assert!(recovered.is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_unrecoverable_file_reports_parse_error() {
+        let dir = temp_dir();
+        let path = dir.join("settings.json");
+        fs::write(&path, b"{").unwrap();
+        fs::write(sibling(&path, "bak"), b"also broken").unwrap();
+
+        let error = read_json::<AppSettings>(&path, "settings").err().unwrap();
+        assert!(error.message.starts_with("Failed to parse settings"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{");
+        fs::remove_dir_all(dir).unwrap();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,450 @@
+use crate::domain::error::LilyError;
+use crate::domain::interfaces::FileStorageTrait;
+use crate::domain::models::{ChatMessage, ServerSettings, TTSParameters};
+use crate::infrastructure::chat_stream::ChatStream;
+use log::warn;
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+const USER_ID: &str = "default_user";
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChatRequest {
+    pub message: String,
+    pub user_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tts: Option<TtsRequest>,
+    /// Lets Lily-Core match a later cancel notice to this request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TtsRequest {
+    pub enabled: bool,
+    pub params: TTSParameters,
+}
+
+impl ChatRequest {
+    pub fn new(message: String, tts_params: Option<TTSParameters>) -> Self {
+        Self {
+            message,
+            user_id: USER_ID.to_string(),
+            tts: tts_params.map(|params| TtsRequest { enabled: true, params }),
+            request_id: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: String) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct CancelRequest<'a> {
+    user_id: &'a str,
+    request_id: &'a str,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatResponse {
+    pub response: String,
+    #[serde(default)]
+    pub timestamp: String,
+}
+
+// Entries of `GET /conversation/{user_id}`; unlike `ChatMessage` the role is required
+#[derive(Deserialize)]
+struct ConversationResponse {
+    conversation: Vec<ConversationEntry>,
+}
+
+#[derive(Deserialize)]
+struct ConversationEntry {
+    role: String,
+    content: String,
+    #[serde(default)]
+    timestamp: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonitoringSnapshot {
+    pub status: String,
+    pub service_name: String,
+    pub version: String,
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<SystemMetrics>,
+    #[serde(default)]
+    pub services: Vec<ServiceStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SystemMetrics {
+    pub cpu_usage: Option<f64>,
+    pub memory_usage: Option<f64>,
+    pub disk_usage: Option<f64>,
+    pub uptime: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    #[serde(default)]
+    pub last_updated: String,
+}
+
+/// The most recent agent run; everything but `exists` is absent when there is none.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AgentLoop {
+    pub exists: bool,
+    pub user_id: String,
+    pub user_message: String,
+    pub final_response: String,
+    pub completed: bool,
+    pub start_time: String,
+    pub end_time: String,
+    pub duration_seconds: Option<f64>,
+    pub steps: Vec<AgentStep>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AgentStep {
+    pub step_number: u32,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub reasoning: String,
+    pub tool_name: Option<String>,
+    pub tool_parameters: serde_json::Value,
+    pub tool_result: serde_json::Value,
+    pub timestamp: String,
+}
+
+/// How long each kind of call may take; chat covers a whole agent run, while a
+/// streamed reply may run as long as it keeps sending within `stream_idle`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LilyCoreTimeouts {
+    pub chat: Duration,
+    pub query: Duration,
+    pub stream_idle: Duration,
+}
+
+impl Default for LilyCoreTimeouts {
+    fn default() -> Self {
+        Self {
+            chat: Duration::from_secs(120),
+            query: Duration::from_secs(10),
+            stream_idle: Duration::from_secs(60),
+        }
+    }
+}
+
+/// HTTP client for Lily-Core. Clones share one connection pool.
+#[derive(Clone)]
+pub struct LilyCoreClient {
+    http: reqwest::Client,
+    server_settings: Option<ServerSettings>,
+    storage: Option<Arc<dyn FileStorageTrait>>,
+    timeouts: LilyCoreTimeouts,
+}
+
+impl Default for LilyCoreClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LilyCoreClient {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            server_settings: None,
+            storage: None,
+            timeouts: LilyCoreTimeouts::default(),
+        }
+    }
+
+    /// Pins the endpoint instead of re-reading the saved settings on every call.
+    pub fn with_server_settings(mut self, settings: ServerSettings) -> Self {
+        self.server_settings = Some(settings);
+        self
+    }
+
+    /// Reads the endpoint from the saved settings. Without it, or pinned
+    /// settings, the default Lily-Core URL is used.
+    pub fn with_storage(mut self, storage: Arc<dyn FileStorageTrait>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn with_timeouts(mut self, timeouts: LilyCoreTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub async fn send_chat(&self, request: &ChatRequest) -> Result<ChatResponse, LilyError> {
+        let request = self.request(Method::POST, "/chat", self.timeouts.chat).json(request);
+        Self::parse(request).await
+    }
+
+    /// Starts a streamed reply from `/chat/stream`; read it with `ChatStream::next`.
+    pub async fn stream_chat(&self, request: &ChatRequest) -> Result<ChatStream, LilyError> {
+        let idle = self.timeouts.stream_idle;
+        let request = self
+            .http
+            .post(self.server_settings().http_url("/chat/stream"))
+            .header(reqwest::header::ACCEPT, "text/event-stream, application/x-ndjson")
+            .json(request);
+        let response = tokio::time::timeout(idle, Self::send(request))
+            .await
+            .map_err(|_| LilyError::timeout(format!("Lily-Core did not start streaming within {} seconds", idle.as_secs())))??;
+        Ok(ChatStream::new(response, idle))
+    }
+
+    /// Tells Lily-Core to stop working on a request the user gave up on.
+    pub async fn cancel_chat(&self, request_id: &str) -> Result<(), LilyError> {
+        let body = CancelRequest { user_id: USER_ID, request_id };
+        Self::send(self.request(Method::POST, "/chat/cancel", self.timeouts.query).json(&body)).await?;
+        Ok(())
+    }
+
+    pub async fn get_conversation(&self) -> Result<Vec<ChatMessage>, LilyError> {
+        let path = format!("/conversation/{}", USER_ID);
+        let response: ConversationResponse = Self::parse(self.request(Method::GET, &path, self.timeouts.query)).await?;
+        Ok(response
+            .conversation
+            .into_iter()
+            .map(|entry| ChatMessage {
+                role: entry.role,
+                content: entry.content,
+                timestamp: entry.timestamp,
+                status: None,
+            })
+            .collect())
+    }
+
+    pub async fn clear_conversation(&self) -> Result<(), LilyError> {
+        let path = format!("/conversation/{}", USER_ID);
+        Self::send(self.request(Method::DELETE, &path, self.timeouts.query)).await?;
+        Ok(())
+    }
+
+    pub async fn get_monitoring(&self) -> Result<MonitoringSnapshot, LilyError> {
+        Self::parse(self.request(Method::GET, "/monitoring", self.timeouts.query)).await
+    }
+
+    pub async fn get_agent_loop(&self) -> Result<AgentLoop, LilyError> {
+        Self::parse(self.request(Method::GET, "/agent-loops", self.timeouts.query)).await
+    }
+
+    // Re-read on every call so edits to the server settings apply immediately
+    fn server_settings(&self) -> ServerSettings {
+        if let Some(settings) = &self.server_settings {
+            return settings.clone();
+        }
+        let Some(storage) = &self.storage else {
+            return ServerSettings::default();
+        };
+        storage
+            .load_settings()
+            .map(|settings| settings.server)
+            .unwrap_or_else(|e| {
+                warn!("Failed to load settings, using default Lily-Core URL: {}", e);
+                ServerSettings::default()
+            })
+    }
+
+    fn request(&self, method: Method, path: &str, timeout: Duration) -> RequestBuilder {
+        self.http.request(method, self.server_settings().http_url(path)).timeout(timeout)
+    }
+
+    async fn send(request: RequestBuilder) -> Result<reqwest::Response, LilyError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let message = if body.is_empty() { status.canonical_reason().unwrap_or_default().to_string() } else { body };
+            return Err(LilyError::http(status.as_u16(), message));
+        }
+        Ok(response)
+    }
+
+    async fn parse<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, LilyError> {
+        let body = Self::send(request).await?.bytes().await?;
+        serde_json::from_slice(&body).map_err(|e| LilyError::protocol(format!("Failed to parse response: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::error::ErrorKind;
+    use crate::domain::interfaces::MockFileStorageTrait;
+    use crate::domain::models::AppSettings;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    // Answers one request with `status` and `body`, returning the raw request it received
+    async fn serve_once(status: &'static str, body: &'static str, delay: Duration) -> (LilyCoreClient, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length || read == 0 {
+                        break;
+                    }
+                }
+            }
+            tokio::time::sleep(delay).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            String::from_utf8_lossy(&request).into_owned()
+        });
+
+        let client = LilyCoreClient::new().with_server_settings(ServerSettings {
+            http_base_url: format!("http://{}", address),
+            ..ServerSettings::default()
+        });
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn test_chat_round_trip() {
+        let (client, server) = serve_once("200 OK", r#"{"response":"hi there","timestamp":"2025-01-01T00:00:00Z"}"#, Duration::ZERO).await;
+
+        let request = ChatRequest::new("hello".to_string(), Some(TTSParameters::default()));
+        let response = client.send_chat(&request).await.unwrap();
+        assert_eq!(response.response, "hi there");
+        assert_eq!(response.timestamp, "2025-01-01T00:00:00Z");
+
+        let raw = server.await.unwrap();
+        assert!(raw.starts_with("POST /chat "));
+        let body: serde_json::Value = serde_json::from_str(raw.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["message"], "hello");
+        assert_eq!(body["user_id"], "default_user");
+        assert_eq!(body["tts"]["enabled"], true);
+    }
+
+    #[tokio::test]
+    async fn test_chat_without_tts_omits_field() {
+        let json = serde_json::to_value(ChatRequest::new("hello".to_string(), None)).unwrap();
+        assert_eq!(json, serde_json::json!({ "message": "hello", "user_id": "default_user" }));
+    }
+
+    #[tokio::test]
+    async fn test_conversation_requires_role() {
+        let (client, _server) = serve_once("200 OK", r#"{"conversation":[{"role":"user","content":"hi"}]}"#, Duration::ZERO).await;
+        let messages = client.get_conversation().await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].role, "user");
+
+        let (client, _server) = serve_once("200 OK", r#"{"conversation":[{"content":"hi"}]}"#, Duration::ZERO).await;
+        assert_eq!(client.get_conversation().await.unwrap_err().kind, ErrorKind::Protocol);
+    }
+
+    #[tokio::test]
+    async fn test_http_status_is_reported() {
+        let (client, _server) = serve_once("503 Service Unavailable", "agent busy", Duration::ZERO).await;
+        assert_eq!(client.clear_conversation().await, Err(LilyError::http(503, "agent busy")));
+    }
+
+    #[tokio::test]
+    async fn test_stream_chat_reports_http_status() {
+        let (client, server) = serve_once("404 Not Found", "", Duration::ZERO).await;
+        let error = client.stream_chat(&ChatRequest::new("hello".to_string(), None)).await.err().unwrap();
+        assert_eq!(error, LilyError::http(404, "Not Found"));
+        assert!(server.await.unwrap().starts_with("POST /chat/stream "));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_notice_names_the_request() {
+        let (client, server) = serve_once("200 OK", "{}", Duration::ZERO).await;
+        client.cancel_chat("req-1").await.unwrap();
+
+        let raw = server.await.unwrap();
+        assert!(raw.starts_with("POST /chat/cancel "));
+        let body: serde_json::Value = serde_json::from_str(raw.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body, serde_json::json!({ "user_id": "default_user", "request_id": "req-1" }));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_is_read_from_injected_storage() {
+        let (pinned, server) = serve_once("200 OK", "{}", Duration::ZERO).await;
+        let server_settings = pinned.server_settings();
+        let mut storage = MockFileStorageTrait::new();
+        storage
+            .expect_load_settings()
+            .times(1)
+            .returning(move || Ok(AppSettings { server: server_settings.clone(), ..AppSettings::default() }));
+
+        let client = LilyCoreClient::new().with_storage(Arc::new(storage));
+        client.clear_conversation().await.unwrap();
+        assert!(server.await.unwrap().starts_with("DELETE /conversation/default_user "));
+    }
+
+    #[tokio::test]
+    async fn test_slow_response_times_out() {
+        let (client, _server) = serve_once("200 OK", "{}", Duration::from_secs(2)).await;
+        let client = client.with_timeouts(LilyCoreTimeouts { query: Duration::from_millis(100), ..LilyCoreTimeouts::default() });
+        assert_eq!(client.get_monitoring().await.unwrap_err().kind, ErrorKind::Timeout);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_server_is_a_network_error() {
+        let client = LilyCoreClient::new().with_server_settings(ServerSettings {
+            http_base_url: "http://127.0.0.1:1".to_string(),
+            ..ServerSettings::default()
+        });
+        assert_eq!(client.get_agent_loop().await.unwrap_err().kind, ErrorKind::Network);
+    }
+
+    #[test]
+    fn test_monitoring_and_agent_loop_decode() {
+        let snapshot: MonitoringSnapshot = serde_json::from_str(
+            r#"{"status":"ok","service_name":"lily-core","version":"1.2","timestamp":"t","metrics":{"cpu_usage":12.5},"services":[{"name":"tts","status":"up"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(snapshot.metrics.unwrap().cpu_usage, Some(12.5));
+        assert_eq!(snapshot.services[0].name, "tts");
+
+        let idle: AgentLoop = serde_json::from_str(r#"{"exists":false,"message":"No agent loop yet"}"#).unwrap();
+        assert!(!idle.exists);
+        assert!(idle.steps.is_empty());
+
+        let run: AgentLoop = serde_json::from_str(
+            r#"{"exists":true,"completed":true,"steps":[{"step_number":1,"type":"tool_call","tool_name":"search","tool_parameters":{"q":"x"}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(run.steps[0].kind, "tool_call");
+        assert_eq!(run.steps[0].tool_name.as_deref(), Some("search"));
+    }
+}
@@ -1,2 +1,7 @@
+pub mod chat_stream;
 pub mod file_storage;
+pub mod lily_core;
+pub mod protocol;
+pub mod sqlite_storage;
+pub mod storage_paths;
 pub mod websocket;
\ No newline at end of file
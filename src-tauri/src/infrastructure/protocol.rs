@@ -0,0 +1,213 @@
+use crate::services::audio_pipeline::StreamFormat;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Wire format spoken with Lily-Core over the WebSocket. Text frames are
+// `kind` or `kind:payload`; binary frames carry TTS audio.
+const REGISTER_PREFIX: &str = "register:";
+const AUDIO_START_PREFIX: &str = "audio_start:";
+const TRANSCRIPTION_PREFIX: &str = "transcription:";
+const ERROR_PREFIX: &str = "error:";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Register { user_id: String },
+    Ping,
+    /// Handshake sent before the first audio frame describing the binary format.
+    AudioStart(StreamFormat),
+    /// The user talked over the reply; the server should cancel generation.
+    Interrupt,
+}
+
+impl ClientMessage {
+    pub fn to_text(&self) -> String {
+        match self {
+            ClientMessage::Register { user_id } => format!("{}{}", REGISTER_PREFIX, user_id),
+            ClientMessage::Ping => "ping".to_string(),
+            ClientMessage::Interrupt => "interrupt".to_string(),
+            ClientMessage::AudioStart(format) => format!(
+                "{}{}",
+                AUDIO_START_PREFIX,
+                serde_json::to_string(format).unwrap_or_default()
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptionKind {
+    Interim,
+    Final,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transcription {
+    #[serde(rename = "type")]
+    pub kind: TranscriptionKind,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServerError {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    Registered,
+    Pong,
+    Transcription(Transcription),
+    TtsAudio(Vec<u8>),
+    Error(ServerError),
+    Unknown(String),
+}
+
+/// A message that used a known prefix but whose payload could not be decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolError {
+    pub raw: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Malformed server message ({}): {}", self.reason, self.raw)
+    }
+}
+
+impl ServerMessage {
+    /// Parses a text frame. Known messages with a malformed payload are an
+    /// error; anything unrecognised falls back to `ServerMessage::Unknown`.
+    pub fn parse_text(text: &str) -> Result<Self, ProtocolError> {
+        let malformed = |reason: String| ProtocolError {
+            raw: text.to_string(),
+            reason,
+        };
+
+        match text {
+            "registered" => return Ok(ServerMessage::Registered),
+            "pong" => return Ok(ServerMessage::Pong),
+            _ => {}
+        }
+
+        if let Some(payload) = text.strip_prefix(TRANSCRIPTION_PREFIX) {
+            let transcription: Transcription = serde_json::from_str(payload)
+                .map_err(|e| malformed(format!("invalid transcription payload: {}", e)))?;
+            return Ok(ServerMessage::Transcription(transcription));
+        }
+
+        if let Some(payload) = text.strip_prefix(ERROR_PREFIX) {
+            let payload = payload.trim();
+            // Structured errors are JSON; older servers send a plain string
+            let error = if payload.starts_with('{') {
+                serde_json::from_str(payload)
+                    .map_err(|e| malformed(format!("invalid error payload: {}", e)))?
+            } else {
+                ServerError {
+                    message: payload.to_string(),
+                    code: None,
+                }
+            };
+            return Ok(ServerMessage::Error(error));
+        }
+
+        Ok(ServerMessage::Unknown(text.to_string()))
+    }
+
+    pub fn from_binary(data: Vec<u8>) -> Self {
+        ServerMessage::TtsAudio(data)
+    }
+}
+
+// Payload of the `registration` event
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RegistrationEvent {
+    pub registered: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::audio_pipeline::StreamEncoding;
+
+    #[test]
+    fn test_client_messages_encode() {
+        let register = ClientMessage::Register { user_id: "default_user".to_string() };
+        assert_eq!(register.to_text(), "register:default_user");
+        assert_eq!(ClientMessage::Ping.to_text(), "ping");
+        assert_eq!(ClientMessage::Interrupt.to_text(), "interrupt");
+    }
+
+    #[test]
+    fn test_audio_start_handshake_encodes_format() {
+        let handshake = ClientMessage::AudioStart(StreamFormat {
+            encoding: StreamEncoding::Pcm16,
+            sample_rate: 16_000,
+            channels: 1,
+        });
+        assert_eq!(
+            handshake.to_text(),
+            r#"audio_start:{"encoding":"pcm16","sample_rate":16000,"channels":1}"#
+        );
+    }
+
+    #[test]
+    fn test_parse_control_messages() {
+        assert_eq!(ServerMessage::parse_text("registered"), Ok(ServerMessage::Registered));
+        assert_eq!(ServerMessage::parse_text("pong"), Ok(ServerMessage::Pong));
+    }
+
+    #[test]
+    fn test_parse_transcriptions() {
+        let interim = ServerMessage::parse_text(r#"transcription:{"type":"interim","text":"hel"}"#).unwrap();
+        assert_eq!(interim, ServerMessage::Transcription(Transcription {
+            kind: TranscriptionKind::Interim,
+            text: "hel".to_string(),
+        }));
+
+        let final_ = ServerMessage::parse_text(r#"transcription:{"type":"final","text":"hello"}"#).unwrap();
+        assert_eq!(final_, ServerMessage::Transcription(Transcription {
+            kind: TranscriptionKind::Final,
+            text: "hello".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_malformed_transcription_is_an_error() {
+        assert!(ServerMessage::parse_text("transcription:{not json").is_err());
+        assert!(ServerMessage::parse_text(r#"transcription:{"type":"partial","text":"x"}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let structured = ServerMessage::parse_text(r#"error:{"message":"stt unavailable","code":"stt"}"#).unwrap();
+        assert_eq!(structured, ServerMessage::Error(ServerError {
+            message: "stt unavailable".to_string(),
+            code: Some("stt".to_string()),
+        }));
+
+        let plain = ServerMessage::parse_text("error: user not registered").unwrap();
+        assert_eq!(plain, ServerMessage::Error(ServerError {
+            message: "user not registered".to_string(),
+            code: None,
+        }));
+    }
+
+    #[test]
+    fn test_unknown_messages_fall_back() {
+        assert_eq!(
+            ServerMessage::parse_text("something-new:42"),
+            Ok(ServerMessage::Unknown("something-new:42".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_transcription_serializes_with_type_field() {
+        let transcription = Transcription { kind: TranscriptionKind::Final, text: "hi".to_string() };
+        let json = serde_json::to_value(&transcription).unwrap();
+        assert_eq!(json, serde_json::json!({ "type": "final", "text": "hi" }));
+    }
+}
@@ -0,0 +1,553 @@
+use crate::domain::error::LilyError;
+use crate::domain::interfaces::FileStorageTrait;
+use crate::domain::models::{AppSettings, ChatMessage, LogEntry, MessageStatus, StorageRecovered};
+use crate::infrastructure::file_storage::FileStorage;
+use crate::infrastructure::storage_paths::StoragePaths;
+use crate::services::events::EventSink;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+const MAX_LOGS: i64 = 1000;
+const SETTINGS_KEY: &str = "app";
+// Set once the JSON files from `FileStorage` have been copied in
+const IMPORT_MARKER_KEY: &str = "json_imported_at";
+// The UI keeps a single local conversation for now
+const DEFAULT_CONVERSATION: i64 = 1;
+
+// Applied in order inside a transaction; `PRAGMA user_version` counts how many have run.
+// Never edit a shipped migration, append a new one.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE conversations (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE messages (
+        id INTEGER PRIMARY KEY,
+        conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        status TEXT
+    );
+    CREATE UNIQUE INDEX messages_by_position ON messages(conversation_id, position);
+    CREATE TABLE logs (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        id TEXT NOT NULL UNIQUE,
+        timestamp TEXT NOT NULL,
+        type TEXT NOT NULL,
+        message TEXT NOT NULL,
+        details TEXT
+    );
+    CREATE INDEX logs_by_timestamp ON logs(timestamp);
+    CREATE INDEX logs_by_type ON logs(type);",
+];
+
+/// Settings, chat history and logs in a SQLite database next to the JSON
+/// files, which are imported the first time it is opened.
+pub struct SqliteStorage {
+    path: Option<PathBuf>,
+    // The JSON files to import from; recoveries while reading them are reported through it
+    legacy: Option<FileStorage>,
+    // Opened on first use
+    connection: Mutex<Option<Connection>>,
+}
+
+impl SqliteStorage {
+    pub fn new(paths: StoragePaths) -> Self {
+        Self {
+            path: Some(paths.database_file()),
+            legacy: Some(FileStorage::new(paths)),
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// A private database that lives as long as this value, for tests and
+    /// throwaway sessions. Nothing is imported into it.
+    pub fn in_memory() -> Result<Self, LilyError> {
+        let mut connection = Connection::open_in_memory()?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            path: None,
+            legacy: None,
+            connection: Mutex::new(Some(connection)),
+        })
+    }
+
+    /// Emits `storage-recovered` when a JSON file being imported had to be restored.
+    pub fn with_event_sink(mut self, events: Arc<dyn EventSink>) -> Self {
+        self.legacy = self.legacy.map(|legacy| legacy.with_event_sink(events));
+        self
+    }
+
+    /// Opens the database at `path`, migrating it to the latest schema.
+    pub fn open(path: &Path) -> Result<Connection, LilyError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| LilyError::storage(format!("Failed to create directories: {}", e)))?;
+        }
+        let mut connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+        Ok(connection)
+    }
+
+    /// The most recent `limit` log entries, oldest first.
+    pub fn recent_logs(&self, limit: usize) -> Result<Vec<LogEntry>, LilyError> {
+        self.with_connection(|connection| {
+            let mut logs = query_logs(connection, "ORDER BY seq DESC LIMIT ?1", params![limit as i64])?;
+            logs.reverse();
+            Ok(logs)
+        })
+    }
+
+    fn with_connection<T>(&self, f: impl FnOnce(&mut Connection) -> Result<T, LilyError>) -> Result<T, LilyError> {
+        // SQLite keeps the data consistent even if a caller panicked while holding the lock
+        let mut guard = self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if guard.is_none() {
+            if let Some(path) = &self.path {
+                let mut connection = Self::open(path)?;
+                if let Some(legacy) = &self.legacy {
+                    import_json_files(&mut connection, legacy)?;
+                }
+                *guard = Some(connection);
+            }
+        }
+        match guard.as_mut() {
+            Some(connection) => f(connection),
+            None => Err(LilyError::storage("Database connection unavailable")),
+        }
+    }
+}
+
+impl FileStorageTrait for SqliteStorage {
+    fn save_settings(&self, settings: AppSettings) -> Result<(), LilyError> {
+        self.with_connection(|connection| save_settings(connection, &settings))
+    }
+
+    fn load_settings(&self) -> Result<AppSettings, LilyError> {
+        self.with_connection(|connection| load_settings(connection))
+    }
+
+    fn save_chat_history(&self, messages: Vec<ChatMessage>) -> Result<(), LilyError> {
+        self.with_connection(|connection| {
+            let transaction = connection.transaction()?;
+            save_chat_history(&transaction, &messages)?;
+            transaction.commit()?;
+            Ok(())
+        })
+    }
+
+    fn load_chat_history(&self) -> Result<Vec<ChatMessage>, LilyError> {
+        self.with_connection(|connection| load_chat_history(connection))
+    }
+
+    fn clear_chat_history(&self) -> Result<(), LilyError> {
+        self.with_connection(|connection| {
+            connection.execute("DELETE FROM messages WHERE conversation_id = ?1", [DEFAULT_CONVERSATION])?;
+            Ok(())
+        })
+    }
+
+    fn add_log_entry(&self, type_: String, message: String, details: Option<serde_json::Value>) -> Result<(), LilyError> {
+        let entry = LogEntry {
+            id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            type_,
+            message,
+            details,
+        };
+        self.with_connection(|connection| add_log_entry(connection, &entry))
+    }
+
+    fn get_logs(&self) -> Result<Vec<LogEntry>, LilyError> {
+        self.with_connection(|connection| query_logs(connection, "ORDER BY seq", []))
+    }
+
+    fn clear_logs(&self) -> Result<(), LilyError> {
+        self.with_connection(|connection| {
+            connection.execute("DELETE FROM logs", [])?;
+            Ok(())
+        })
+    }
+
+    fn take_recoveries(&self) -> Vec<StorageRecovered> {
+        self.legacy.as_ref().map(FileStorage::take_recoveries).unwrap_or_default()
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), LilyError> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(LilyError::storage(format!(
+            "Database schema version {} is newer than this build supports ({})",
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+        info!("Applied storage migration {}", index + 1);
+    }
+    Ok(())
+}
+
+// One-time copy of the JSON files so upgrading keeps settings, history and logs.
+// A file that fails to parse is skipped rather than blocking startup; the files
+// themselves are left in place.
+fn import_json_files(connection: &mut Connection, legacy: &FileStorage) -> Result<(), LilyError> {
+    let imported: Option<String> = connection
+        .query_row("SELECT value FROM settings WHERE key = ?1", [IMPORT_MARKER_KEY], |row| row.get(0))
+        .optional()?;
+    if imported.is_some() {
+        return Ok(());
+    }
+
+    let settings = legacy.load_settings()
+        .map_err(|e| warn!("Not importing settings.json: {}", e))
+        .ok();
+    let history = legacy.load_chat_history()
+        .map_err(|e| warn!("Not importing chat_history.json: {}", e))
+        .unwrap_or_default();
+    let logs = legacy.get_logs()
+        .map_err(|e| warn!("Not importing logs.json: {}", e))
+        .unwrap_or_default();
+
+    import(connection, settings.as_ref(), &history, &logs)
+}
+
+fn import(connection: &mut Connection, settings: Option<&AppSettings>, history: &[ChatMessage], logs: &[LogEntry]) -> Result<(), LilyError> {
+    let transaction = connection.transaction()?;
+    if let Some(settings) = settings {
+        save_settings(&transaction, settings)?;
+    }
+    save_chat_history(&transaction, history)?;
+    for entry in logs {
+        add_log_entry(&transaction, entry)?;
+    }
+    transaction.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)",
+        params![IMPORT_MARKER_KEY, Utc::now().to_rfc3339()],
+    )?;
+    transaction.commit()?;
+    info!("Imported {} messages and {} log entries from JSON storage", history.len(), logs.len());
+    Ok(())
+}
+
+fn save_settings(connection: &Connection, settings: &AppSettings) -> Result<(), LilyError> {
+    let json = serde_json::to_string(settings)
+        .map_err(|e| LilyError::storage(format!("Failed to serialize settings: {}", e)))?;
+    connection.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![SETTINGS_KEY, json],
+    )?;
+    Ok(())
+}
+
+fn load_settings(connection: &Connection) -> Result<AppSettings, LilyError> {
+    let json: Option<String> = connection
+        .query_row("SELECT value FROM settings WHERE key = ?1", [SETTINGS_KEY], |row| row.get(0))
+        .optional()?;
+    match json {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| LilyError::storage(format!("Failed to parse settings: {}", e))),
+        None => Ok(AppSettings::default()),
+    }
+}
+
+// The UI saves the whole history after every message, so only the part that
+// differs from what is stored gets rewritten
+fn save_chat_history(connection: &Connection, messages: &[ChatMessage]) -> Result<(), LilyError> {
+    connection.execute(
+        "INSERT OR IGNORE INTO conversations (id, title, created_at) VALUES (?1, 'default', ?2)",
+        params![DEFAULT_CONVERSATION, Utc::now().to_rfc3339()],
+    )?;
+
+    let stored = load_chat_history(connection)?;
+    let unchanged = stored.iter().zip(messages).take_while(|(stored, message)| stored == message).count();
+
+    connection.execute(
+        "DELETE FROM messages WHERE conversation_id = ?1 AND position >= ?2",
+        params![DEFAULT_CONVERSATION, unchanged as i64],
+    )?;
+    let mut insert = connection.prepare_cached(
+        "INSERT INTO messages (conversation_id, position, role, content, timestamp, status)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for (position, message) in messages.iter().enumerate().skip(unchanged) {
+        let status = message.status.map(status_to_text).transpose()?;
+        insert.execute(params![
+            DEFAULT_CONVERSATION,
+            position as i64,
+            message.role,
+            message.content,
+            message.timestamp,
+            status
+        ])?;
+    }
+    Ok(())
+}
+
+fn load_chat_history(connection: &Connection) -> Result<Vec<ChatMessage>, LilyError> {
+    let mut statement = connection.prepare_cached(
+        "SELECT role, content, timestamp, status FROM messages WHERE conversation_id = ?1 ORDER BY position",
+    )?;
+    let rows = statement.query_map([DEFAULT_CONVERSATION], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<String>>(3)?))
+    })?;
+
+    let mut messages = Vec::new();
+    for row in rows {
+        let (role, content, timestamp, status) = row?;
+        messages.push(ChatMessage {
+            role,
+            content,
+            timestamp,
+            status: status.map(|status| status_from_text(&status)).transpose()?,
+        });
+    }
+    Ok(messages)
+}
+
+fn add_log_entry(connection: &Connection, entry: &LogEntry) -> Result<(), LilyError> {
+    let details = entry
+        .details
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| LilyError::storage(format!("Failed to serialize log details: {}", e)))?;
+    connection
+        .prepare_cached("INSERT OR IGNORE INTO logs (id, timestamp, type, message, details) VALUES (?1, ?2, ?3, ?4, ?5)")?
+        .execute(params![entry.id, entry.timestamp.to_rfc3339(), entry.type_, entry.message, details])?;
+    // Same cap as the JSON log file
+    connection
+        .prepare_cached("DELETE FROM logs WHERE seq <= (SELECT MAX(seq) FROM logs) - ?1")?
+        .execute([MAX_LOGS])?;
+    Ok(())
+}
+
+fn query_logs(connection: &Connection, order: &str, params: impl rusqlite::Params) -> Result<Vec<LogEntry>, LilyError> {
+    let mut statement = connection.prepare_cached(&format!("SELECT id, timestamp, type, message, details FROM logs {}", order))?;
+    let rows = statement.query_map(params, |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+
+    let mut logs = Vec::new();
+    for row in rows {
+        let (id, timestamp, type_, message, details) = row?;
+        logs.push(LogEntry {
+            id,
+            timestamp: DateTime::parse_from_rfc3339(&timestamp)
+                .map_err(|e| LilyError::storage(format!("Invalid log timestamp '{}': {}", timestamp, e)))?
+                .with_timezone(&Utc),
+            type_,
+            message,
+            details: details
+                .map(|details| serde_json::from_str(&details))
+                .transpose()
+                .map_err(|e| LilyError::storage(format!("Failed to parse log details: {}", e)))?,
+        });
+    }
+    Ok(logs)
+}
+
+fn status_to_text(status: MessageStatus) -> Result<String, LilyError> {
+    match serde_json::to_value(status) {
+        Ok(serde_json::Value::String(text)) => Ok(text),
+        _ => Err(LilyError::storage(format!("Failed to serialize message status {:?}", status))),
+    }
+}
+
+fn status_from_text(text: &str) -> Result<MessageStatus, LilyError> {
+    serde_json::from_value(serde_json::Value::String(text.to_string()))
+        .map_err(|e| LilyError::storage(format!("Unknown message status '{}': {}", text, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> Connection {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        connection
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("lily-sqlite-{}", Uuid::new_v4()))
+    }
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            status: None,
+        }
+    }
+
+    fn log(id: &str, message: &str) -> LogEntry {
+        LogEntry {
+            id: id.to_string(),
+            timestamp: Utc::now(),
+            type_: "info".to_string(),
+            message: message.to_string(),
+            details: Some(serde_json::json!({ "n": 1 })),
+        }
+    }
+
+    #[test]
+    fn test_migrations_are_recorded_and_idempotent() {
+        let mut connection = database();
+        let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+
+        migrate(&mut connection).unwrap();
+
+        connection.pragma_update(None, "user_version", MIGRATIONS.len() + 1).unwrap();
+        assert!(migrate(&mut connection).is_err());
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let connection = database();
+        assert_eq!(serde_json::to_value(load_settings(&connection).unwrap()).unwrap(), serde_json::to_value(AppSettings::default()).unwrap());
+
+        let mut settings = AppSettings::default();
+        settings.tts_enabled = !settings.tts_enabled;
+        save_settings(&connection, &settings).unwrap();
+        save_settings(&connection, &settings).unwrap();
+        assert_eq!(load_settings(&connection).unwrap().tts_enabled, settings.tts_enabled);
+    }
+
+    #[test]
+    fn test_chat_history_rewrites_only_what_changed() {
+        let connection = database();
+        let mut history = vec![message("user", "hi"), message("assistant", "hello")];
+        save_chat_history(&connection, &history).unwrap();
+        let first_id: i64 = connection.query_row("SELECT id FROM messages WHERE position = 0", [], |row| row.get(0)).unwrap();
+
+        history.push(ChatMessage { status: Some(MessageStatus::Cancelled), ..message("assistant", "Request cancelled.") });
+        save_chat_history(&connection, &history).unwrap();
+        assert_eq!(load_chat_history(&connection).unwrap(), history);
+        // The unchanged prefix keeps its rows
+        let id: i64 = connection.query_row("SELECT id FROM messages WHERE position = 0", [], |row| row.get(0)).unwrap();
+        assert_eq!(id, first_id);
+
+        history.truncate(1);
+        save_chat_history(&connection, &history).unwrap();
+        assert_eq!(load_chat_history(&connection).unwrap(), history);
+    }
+
+    #[test]
+    fn test_logs_are_capped_and_ordered() {
+        let connection = database();
+        for n in 0..MAX_LOGS + 5 {
+            add_log_entry(&connection, &log(&n.to_string(), &format!("entry {}", n))).unwrap();
+        }
+
+        let logs = query_logs(&connection, "ORDER BY seq", []).unwrap();
+        assert_eq!(logs.len() as i64, MAX_LOGS);
+        assert_eq!(logs[0].message, "entry 5");
+        assert_eq!(logs.last().unwrap().details, Some(serde_json::json!({ "n": 1 })));
+
+        let recent = query_logs(&connection, "ORDER BY seq DESC LIMIT ?1", params![2]).unwrap();
+        assert_eq!(recent[0].message, format!("entry {}", MAX_LOGS + 4));
+    }
+
+    #[test]
+    fn test_json_import_runs_once() {
+        let mut connection = database();
+        let mut settings = AppSettings::default();
+        settings.tts_enabled = !settings.tts_enabled;
+        let history = vec![message("user", "from json")];
+        let logs = vec![log("a", "old log")];
+
+        import(&mut connection, Some(&settings), &history, &logs).unwrap();
+        assert_eq!(load_settings(&connection).unwrap().tts_enabled, settings.tts_enabled);
+        assert_eq!(load_chat_history(&connection).unwrap(), history);
+        assert_eq!(query_logs(&connection, "ORDER BY seq", []).unwrap()[0].id, "a");
+
+        // Already marked, so the JSON files are not read again
+        let dir = temp_dir();
+        let legacy = FileStorage::new(StoragePaths::new(&dir));
+        legacy.save_chat_history(history).unwrap();
+        save_chat_history(&connection, &[]).unwrap();
+        import_json_files(&mut connection, &legacy).unwrap();
+        assert!(load_chat_history(&connection).unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_first_use_imports_json_from_its_directory() {
+        let dir = temp_dir();
+        let legacy = FileStorage::new(StoragePaths::new(&dir));
+        let mut settings = AppSettings::default();
+        legacy.save_settings(settings.clone()).unwrap();
+        settings.tts_enabled = !settings.tts_enabled;
+        legacy.save_settings(settings.clone()).unwrap();
+        legacy.save_chat_history(vec![message("user", "from json")]).unwrap();
+        // Torn write, so the import falls back to the previous settings
+        fs::write(StoragePaths::new(&dir).settings_file(), b"{").unwrap();
+
+        let storage = SqliteStorage::new(StoragePaths::new(&dir));
+        assert_eq!(storage.load_chat_history().unwrap(), vec![message("user", "from json")]);
+        assert_eq!(storage.load_settings().unwrap().tts_enabled, !settings.tts_enabled);
+        let recoveries = storage.take_recoveries();
+        assert_eq!(recoveries.len(), 1);
+        assert_eq!(recoveries[0].file, "settings.json");
+        assert!(storage.take_recoveries().is_empty());
+
+        drop(storage);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_in_memory_storage_is_private() {
+        let first = SqliteStorage::in_memory().unwrap();
+        let second = SqliteStorage::in_memory().unwrap();
+        first.save_chat_history(vec![message("user", "hi")]).unwrap();
+        first.add_log_entry("info".to_string(), "saved".to_string(), None).unwrap();
+
+        assert_eq!(first.load_chat_history().unwrap().len(), 1);
+        assert_eq!(first.recent_logs(5).unwrap()[0].message, "saved");
+        assert!(second.load_chat_history().unwrap().is_empty());
+        assert!(second.take_recoveries().is_empty());
+    }
+
+    #[test]
+    fn test_open_creates_database_file() {
+        let dir = temp_dir();
+        let path = StoragePaths::new(&dir).database_file();
+        {
+            let connection = SqliteStorage::open(&path).unwrap();
+            save_settings(&connection, &AppSettings::default()).unwrap();
+        }
+        let connection = SqliteStorage::open(&path).unwrap();
+        assert!(load_settings(&connection).is_ok());
+        drop(connection);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+use crate::domain::error::LilyError;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Overrides the data directory, e.g. to run isolated profiles side by side.
+pub const DATA_DIR_ENV: &str = "LILY_UI_DATA_DIR";
+/// A file with this name next to the executable turns on portable mode.
+pub const PORTABLE_MARKER: &str = "portable";
+// Portable data lives in this directory next to the executable
+const PORTABLE_DIR: &str = "data";
+
+/// Where settings, history, logs and the database are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoragePaths {
+    root: PathBuf,
+}
+
+impl StoragePaths {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Picks the data directory, in order of precedence: `data_dir` (the
+    /// `--data-dir` flag), `LILY_UI_DATA_DIR`, portable mode (requested or
+    /// marked by a `portable` file next to the executable), then the
+    /// platform data directory.
+    pub fn resolve(data_dir: Option<PathBuf>, portable: bool) -> Result<Self, LilyError> {
+        let exe_dir = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+        Self::resolve_from(data_dir, std::env::var_os(DATA_DIR_ENV), portable, exe_dir)
+    }
+
+    /// Like `resolve`, reading `--data-dir <dir>` and `--portable` from
+    /// command-line arguments. Unrelated arguments are ignored.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, LilyError> {
+        let (data_dir, portable) = parse_args(args);
+        Self::resolve(data_dir, portable)
+    }
+
+    fn resolve_from(data_dir: Option<PathBuf>, env: Option<OsString>, portable: bool, exe_dir: Option<PathBuf>) -> Result<Self, LilyError> {
+        if let Some(dir) = data_dir {
+            return Ok(Self::new(dir));
+        }
+        if let Some(dir) = env.filter(|dir| !dir.is_empty()) {
+            return Ok(Self::new(dir));
+        }
+        if portable || exe_dir.as_ref().is_some_and(|dir| dir.join(PORTABLE_MARKER).exists()) {
+            let exe_dir = exe_dir.ok_or_else(|| LilyError::storage("Could not determine the executable directory for portable mode"))?;
+            return Ok(Self::new(exe_dir.join(PORTABLE_DIR)));
+        }
+        let data_dir = dirs::data_dir().ok_or_else(|| LilyError::storage("Could not determine app data directory"))?;
+        Ok(Self::new(data_dir.join("NsTut").join("LilyUI")))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn settings_file(&self) -> PathBuf {
+        self.root.join("settings.json")
+    }
+
+    pub fn chat_history_file(&self) -> PathBuf {
+        self.root.join("chat_history.json")
+    }
+
+    pub fn logs_file(&self) -> PathBuf {
+        self.root.join("logs.json")
+    }
+
+    pub fn database_file(&self) -> PathBuf {
+        self.root.join("lily.db")
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> (Option<PathBuf>, bool) {
+    let mut data_dir = None;
+    let mut portable = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--portable" {
+            portable = true;
+        } else if arg == "--data-dir" {
+            data_dir = args.next().map(PathBuf::from);
+        } else if let Some(dir) = arg.strip_prefix("--data-dir=") {
+            data_dir = Some(PathBuf::from(dir));
+        }
+    }
+    (data_dir, portable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_beats_environment_beats_portable() {
+        let exe_dir = Some(PathBuf::from("/opt/lily"));
+        let env = Some(OsString::from("/tmp/qa-profile"));
+
+        let paths = StoragePaths::resolve_from(Some(PathBuf::from("/tmp/flag")), env.clone(), true, exe_dir.clone()).unwrap();
+        assert_eq!(paths.root(), Path::new("/tmp/flag"));
+
+        let paths = StoragePaths::resolve_from(None, env, true, exe_dir.clone()).unwrap();
+        assert_eq!(paths.root(), Path::new("/tmp/qa-profile"));
+
+        let paths = StoragePaths::resolve_from(None, Some(OsString::new()), true, exe_dir).unwrap();
+        assert_eq!(paths.root(), Path::new("/opt/lily/data"));
+    }
+
+    #[test]
+    fn test_portable_marker_next_to_executable() {
+        let exe_dir = std::env::temp_dir().join(format!("lily-exe-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&exe_dir).unwrap();
+
+        let paths = StoragePaths::resolve_from(None, None, false, Some(exe_dir.clone())).unwrap();
+        assert!(paths.root().ends_with("NsTut/LilyUI"));
+
+        std::fs::write(exe_dir.join(PORTABLE_MARKER), "").unwrap();
+        let paths = StoragePaths::resolve_from(None, None, false, Some(exe_dir.clone())).unwrap();
+        assert_eq!(paths.root(), exe_dir.join("data"));
+        std::fs::remove_dir_all(exe_dir).unwrap();
+    }
+
+    #[test]
+    fn test_reads_data_dir_from_arguments() {
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&["lily-ui"]), (None, false));
+        assert_eq!(args(&["lily-ui", "--data-dir", "/tmp/a", "--other"]), (Some(PathBuf::from("/tmp/a")), false));
+        assert_eq!(args(&["lily-ui", "--portable", "--data-dir=/tmp/b"]), (Some(PathBuf::from("/tmp/b")), true));
+    }
+
+    #[test]
+    fn test_files_live_under_root() {
+        let paths = StoragePaths::new("/data/lily");
+        assert_eq!(paths.settings_file(), Path::new("/data/lily/settings.json"));
+        assert_eq!(paths.chat_history_file(), Path::new("/data/lily/chat_history.json"));
+        assert_eq!(paths.logs_file(), Path::new("/data/lily/logs.json"));
+        assert_eq!(paths.database_file(), Path::new("/data/lily/lily.db"));
+    }
+}
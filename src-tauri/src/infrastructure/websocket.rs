@@ -1,270 +1,327 @@
-use crate::domain::interfaces::WebSocketTrait;
-use crate::domain::models::{AppState, WebSocketState, WebSocketStatus};
+use crate::domain::error::LilyError;
+use crate::domain::interfaces::{FileStorageTrait, WebSocketTrait};
+use crate::domain::models::{ConnectionState, ServerSettings, WebSocketState, WebSocketStatus};
+use crate::infrastructure::protocol::{ClientMessage, ProtocolError, RegistrationEvent, ServerError, ServerMessage};
+use crate::services::audio_frames::{FrameOutput, SendFuture};
+use crate::services::audio_playback_service::AudioPlaybackService;
+use crate::services::events::EventSink;
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
 use log::{debug, error, info, warn};
-use tauri::{AppHandle, Emitter, Manager};
 use tokio_tungstenite::tungstenite::protocol::Message;
-use tokio_tungstenite::connect_async;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 use url::Url;
+use chrono::Utc;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
+use tokio::net::TcpStream;
 use tokio::time::{interval, Duration};
 
-pub struct WebSocketService;
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsSink = SplitSink<WsStream, Message>;
+type WsSource = SplitStream<WsStream>;
+
+// Bounded so a stalled socket applies backpressure to senders instead of buffering forever
+const OUTBOUND_QUEUE_CAPACITY: usize = 256;
+
+/// Connection to Lily-Core. Clones share the same socket and state.
+#[derive(Clone)]
+pub struct WebSocketService {
+    state: Arc<Mutex<WebSocketState>>,
+    events: Arc<dyn EventSink>,
+    playback: Option<Arc<AudioPlaybackService>>,
+    server_settings: Option<ServerSettings>,
+    storage: Option<Arc<dyn FileStorageTrait>>,
+}
 
 impl WebSocketTrait for WebSocketService {
-    async fn connect(app_handle: AppHandle) -> Result<(), String> {
-        let state = app_handle.state::<AppState>();
-        let ws_state = state.ws_state.clone();
-        
+    async fn connect(&self) -> Result<(), LilyError> {
+        let mut guard = self.state.lock().await;
+
+        if guard.handler_task.as_ref().is_some_and(|task| !task.is_finished()) {
+            info!("WebSocket handler already running, ignoring connect request");
+            return Ok(());
+        }
+
         // Start WebSocket connection in a background task
-        tauri::async_runtime::spawn(async move {
-            if let Err(e) = WebSocketService::websocket_handler(ws_state, app_handle.clone()).await {
+        let service = self.clone();
+        guard.handler_task = Some(tokio::spawn(async move {
+            if let Err(e) = service.websocket_handler().await {
                 error!("WebSocket handler error: {}", e);
             }
-        });
-        
+        }));
+
         Ok(())
     }
 
-    async fn disconnect(app_handle: AppHandle) -> Result<(), String> {
+    async fn disconnect(&self) -> Result<(), LilyError> {
         info!("WebSocket disconnect requested");
-        
-        let state = app_handle.state::<AppState>();
-        let mut ws_state = state.ws_state.lock().await;
-        
-        info!("Current WebSocket state - Connected: {}, Registered: {}",
-              ws_state.is_connected, ws_state.is_registered);
-        
-        if let Some(stream_arc) = ws_state.stream.take() {
-            info!("Closing WebSocket stream");
-            let mut stream = stream_arc.lock().await;
-            let _ = stream.close(None).await;
+
+        let mut ws_state = self.state.lock().await;
+
+        info!("Current WebSocket state: {:?}", ws_state.connection);
+
+        // Cancel the reconnect loop first so it can't dial again behind our back
+        if let Some(task) = ws_state.handler_task.take() {
+            info!("Cancelling WebSocket handler task");
+            task.abort();
         }
-        
-        ws_state.is_connected = false;
-        ws_state.is_registered = false;
-        
-        info!("WebSocket state updated - Connected: false, Registered: false");
-        
-        // Emit disconnected event
-        if let Some(handle) = &ws_state.app_handle {
-            info!("Emitting websocket-status event - Connected: false, Registered: false");
-            let _ = handle.emit("websocket-status", serde_json::json!({
-                "connected": false,
-                "registered": false
-            }));
+
+        if let Some(outbound) = ws_state.outbound.take() {
+            info!("Closing WebSocket stream");
+            // Queued behind any pending frames; the writer closes the sink after sending it
+            let _ = outbound.send(Message::Close(None)).await;
         }
-        
+
+        ws_state.attempt = 0;
+        ws_state.next_retry_at = None;
+        self.set_connection(&mut ws_state, ConnectionState::Stopped);
+
         Ok(())
     }
 
-    async fn send_message(message: String, app_handle: AppHandle) -> Result<(), String> {
-        let state = app_handle.state::<AppState>();
-        let ws_state = state.ws_state.lock().await;
-        
-        if let Some(stream_arc) = ws_state.stream.clone() {
-            let mut stream = stream_arc.lock().await;
-            stream.send(Message::Text(message)).await
-                .map_err(|e| format!("Failed to send message: {}", e))
-        } else {
-            Err("WebSocket not connected".to_string())
-        }
+    async fn send_message(&self, message: String) -> Result<(), LilyError> {
+        self.enqueue(Message::Text(message)).await
     }
 
-    async fn send_binary_data(data: Vec<u8>, app_handle: AppHandle) -> Result<(), String> {
-        let state = app_handle.state::<AppState>();
-        let ws_state = state.ws_state.lock().await;
-        
-        info!("Attempting to send binary data via WebSocket - Data size: {} bytes", data.len());
-
-        if let Some(stream_arc) = ws_state.stream.clone() {
-            let mut stream = stream_arc.lock().await;
-            debug!("Sending binary data with size: {}", data.len());
-            let result = stream.send(Message::Binary(data.clone())).await;
-            
-            match &result {
-                Ok(_) => info!("Successfully sent binary data via WebSocket - Data size: {} bytes", data.len()),
-                Err(e) => error!("Failed to send binary data via WebSocket - Data size: {} bytes, Error: {}", data.len(), e),
-            }
-            
-            result.map_err(|e| format!("Failed to send binary data: {}", e))
-        } else {
-            let error_msg = "WebSocket not connected - No stream available.";
-            error!("{}", error_msg);
-            Err(error_msg.to_string())
+    async fn send_binary_data(&self, data: Vec<u8>) -> Result<(), LilyError> {
+        let size = data.len();
+        debug!("Queueing binary data for WebSocket - Data size: {} bytes", size);
+
+        let result = self.enqueue(Message::Binary(data)).await;
+        if let Err(e) = &result {
+            error!("Failed to send binary data via WebSocket - Data size: {} bytes, Error: {}", size, e);
         }
+
+        result
     }
+}
 
+// Captured audio is streamed over the same socket
+impl FrameOutput for WebSocketService {
+    fn send_frame(&self, frame: Vec<u8>) -> SendFuture<'_> {
+        Box::pin(async move { self.send_binary_data(frame).await.map_err(String::from) })
+    }
 }
 
 impl WebSocketService {
-    pub async fn get_status(app_handle: AppHandle) -> Result<WebSocketStatus, String> {
-        let state = app_handle.state::<AppState>();
-        let ws_state = state.ws_state.lock().await;
-        Ok(WebSocketStatus {
-            connected: ws_state.is_connected,
-            registered: ws_state.is_registered,
-        })
+    pub fn new(events: Arc<dyn EventSink>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(WebSocketState::new())),
+            events,
+            playback: None,
+            server_settings: None,
+            storage: None,
+        }
+    }
+
+    /// Plays TTS replies natively; without it they are forwarded as `websocket-binary`.
+    pub fn with_playback(mut self, playback: Arc<AudioPlaybackService>) -> Self {
+        self.playback = Some(playback);
+        self
+    }
+
+    /// Pins the endpoint instead of re-reading the saved settings on every attempt.
+    pub fn with_server_settings(mut self, settings: ServerSettings) -> Self {
+        self.server_settings = Some(settings);
+        self
+    }
+
+    /// Reads the endpoint and reconnect policy from the saved settings.
+    pub fn with_storage(mut self, storage: Arc<dyn FileStorageTrait>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub async fn get_status(&self) -> WebSocketStatus {
+        self.state.lock().await.status()
+    }
+
+    // Re-read on every attempt so settings changes apply on reconnect
+    fn server_settings(&self) -> ServerSettings {
+        if let Some(settings) = &self.server_settings {
+            return settings.clone();
+        }
+        let Some(storage) = &self.storage else {
+            return ServerSettings::default();
+        };
+        storage
+            .load_settings()
+            .map(|settings| settings.server)
+            .unwrap_or_else(|e| {
+                warn!("Failed to load settings, using default WebSocket URL: {}", e);
+                ServerSettings::default()
+            })
+    }
+
+    // Records a state transition and broadcasts it as `websocket-status`
+    fn set_connection(&self, state: &mut WebSocketState, connection: ConnectionState) {
+        state.connection = connection;
+        let status = state.status();
+        info!("WebSocket state -> {:?} (attempt {})", status.state, status.attempt);
+        self.emit("websocket-status", status);
+    }
+
+    // A failed emit (e.g. while the window closes) must not drop a healthy connection
+    fn emit<T: Serialize>(&self, event: &str, payload: T) {
+        if let Err(e) = self.events.emit(event, payload) {
+            warn!("Failed to emit {}: {}", event, e);
+        }
     }
 
-    async fn websocket_handler(
-        ws_state: Arc<Mutex<WebSocketState>>,
-        app_handle: AppHandle,
-    ) -> Result<(), String> {
-        let url = Url::parse("ws://127.0.0.1:9002")
-            .map_err(|e| format!("Invalid WebSocket URL: {}", e))?;
-        
-        info!("Starting WebSocket handler for URL: {}", url);
-        
-        // Update state with app handle
+    async fn transition(&self, connection: ConnectionState) {
+        let mut state = self.state.lock().await;
+        self.set_connection(&mut state, connection);
+    }
+
+    async fn connect_once(&self, settings: &ServerSettings) -> Result<(), String> {
+        let url = Url::parse(&settings.websocket_url)
+            .map_err(|e| format!("Invalid WebSocket URL '{}': {}", settings.websocket_url, e))?;
+
+        info!("Attempting to connect to WebSocket server at {}", url);
+        let (stream, response) = connect_async(&url).await
+            .map_err(|e| format!("WebSocket connection failed: {}", e))?;
+        info!("WebSocket connected successfully. Response: {:?}", response);
+
+        let (sink, source) = stream.split();
+        let (outbound_tx, outbound_rx) = mpsc::channel(OUTBOUND_QUEUE_CAPACITY);
+
         {
-            let mut state = ws_state.lock().await;
-            state.app_handle = Some(app_handle.clone());
-            info!("WebSocket state updated with app handle");
+            let mut state = self.state.lock().await;
+            state.outbound = Some(outbound_tx);
+            state.attempt = 0;
+            state.next_retry_at = None;
+            self.set_connection(&mut state, ConnectionState::Connected);
         }
-        
-        loop {
-            info!("Attempting to connect to WebSocket server at {}", url);
-            match connect_async(&url).await {
-                Ok((stream, response)) => {
-                    info!("WebSocket connected successfully. Response: {:?}", response);
-                    
-                    {
-                        let stream_arc = Arc::new(Mutex::new(stream));
-                        let mut state = ws_state.lock().await;
-                        state.stream = Some(stream_arc.clone());
-                        state.is_connected = true;
-                        state.is_registered = false;
-                        info!("WebSocket state updated - Connected: true, Registered: false");
-                    }
-                    
-                    // Emit connected event
-                    info!("Emitting websocket-status event - Connected: true, Registered: false");
-                    app_handle.emit("websocket-status", serde_json::json!({
-                        "connected": true,
-                        "registered": false
-                    })).map_err(|e| format!("Failed to emit event: {}", e))?;
-                    
-                    // Start registration process
-                    info!("Starting registration process");
-                    WebSocketService::start_registration(ws_state.clone()).await;
-                    
-                    // Handle messages
-                    info!("Starting message handler");
-                    let message_handler = WebSocketService::handle_messages(ws_state.clone(), app_handle.clone());
-                    
-                    // Start ping task
-                    info!("Starting ping task");
-                    let ping_task = WebSocketService::ping_task(ws_state.clone());
-                    
-                    // Run both tasks concurrently
-                    tokio::select! {
-                        result = message_handler => {
-                            if let Err(e) = result {
-                                error!("Error handling messages: {}", e);
-                            }
-                        }
-                        result = ping_task => {
-                            if let Err(e) = result {
-                                error!("Error in ping task: {}", e);
-                            }
-                        }
-                    }
-                    
-                    info!("Message handler or ping task completed, reconnecting...");
+
+        // Writer owns the sink, so sends never wait on the reader
+        info!("Starting writer task");
+        let mut writer = tokio::spawn(WebSocketService::writer_task(sink, outbound_rx));
+
+        // Registration retries run alongside the reader so "registered" is seen promptly
+        self.transition(ConnectionState::Registering).await;
+        let registration = tokio::spawn(self.clone().start_registration());
+
+        // Handle messages
+        info!("Starting message handler");
+        let message_handler = self.handle_messages(source);
+
+        // Start ping task
+        info!("Starting ping task");
+        let ping_task = self.ping_task();
+
+        // Run all tasks concurrently; whichever ends first tears the connection down
+        tokio::select! {
+            result = message_handler => {
+                if let Err(e) = result {
+                    error!("Error handling messages: {}", e);
                 }
-                Err(e) => {
-                    warn!("WebSocket connection failed: {}. Retrying in 3 seconds...", e);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+            }
+            result = ping_task => {
+                if let Err(e) = result {
+                    error!("Error in ping task: {}", e);
                 }
             }
+            _ = &mut writer => {
+                warn!("WebSocket writer stopped");
+            }
+        }
+
+        registration.abort();
+        writer.abort();
+        {
+            let mut state = self.state.lock().await;
+            state.outbound = None;
+            self.set_connection(&mut state, ConnectionState::Disconnected);
+        }
+
+        Ok(())
+    }
+
+    async fn websocket_handler(&self) -> Result<(), String> {
+        info!("Starting WebSocket handler");
+
+        loop {
+            self.transition(ConnectionState::Connecting).await;
+
+            let settings = self.server_settings();
+            if let Err(e) = self.connect_once(&settings).await {
+                warn!("{}", e);
+            }
+
+            let reconnect = settings.reconnect;
+
+            let mut state = self.state.lock().await;
+            state.attempt += 1;
+            if reconnect.max_attempts.is_some_and(|max| state.attempt > max) {
+                warn!("Giving up after {} reconnect attempts", state.attempt - 1);
+                state.next_retry_at = None;
+                self.set_connection(&mut state, ConnectionState::Stopped);
+                return Ok(());
+            }
+
+            let attempt = state.attempt;
+            let delay = reconnect.delay_for(attempt, rand::random::<f64>());
+            state.next_retry_at = chrono::Duration::from_std(delay).ok().map(|d| Utc::now() + d);
+            self.set_connection(&mut state, ConnectionState::Backoff(attempt));
+            drop(state);
+
+            info!("Reconnecting in {:?} (attempt {})", delay, attempt);
+            tokio::time::sleep(delay).await;
+            self.state.lock().await.next_retry_at = None;
         }
     }
 
-    async fn start_registration(ws_state: Arc<Mutex<WebSocketState>>) {
+    async fn start_registration(self) {
         info!("Starting registration process");
         let mut attempts = 0;
         let max_attempts = 10;
-        
+
         while attempts < max_attempts {
             {
-                let state = ws_state.lock().await;
-                if state.is_registered {
+                let state = self.state.lock().await;
+                if state.is_registered() {
                     info!("Already registered, exiting registration loop");
                     break;
                 }
-                
-                if let Some(stream_arc) = state.stream.clone() {
+
+                if let Some(outbound) = state.outbound.clone() {
+                    drop(state);
                     info!("Sending registration message - Attempt {}/{}", attempts + 1, max_attempts);
-                    let mut stream = stream_arc.lock().await;
-                    if let Err(e) = stream.send(Message::Text("register:default_user".to_string())).await {
-                        warn!("Failed to send registration (server may be unavailable): {}", e);
+                    let register = ClientMessage::Register { user_id: "default_user".to_string() };
+                    if outbound.send(Message::Text(register.to_text())).await.is_err() {
+                        warn!("Failed to send registration (writer has stopped)");
                         break;
                     }
-                    info!("Registration message sent successfully");
+                    info!("Registration message queued successfully");
                 } else {
                     warn!("No WebSocket stream available for registration");
                     break;
                 }
             }
-            
+
             attempts += 1;
             info!("Waiting 2 seconds before next registration attempt");
             tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
         }
-        
+
         if attempts >= max_attempts {
             warn!("Registration attempts exceeded maximum ({})", max_attempts);
         }
     }
 
-    async fn handle_messages(
-        ws_state: Arc<Mutex<WebSocketState>>,
-        app_handle: AppHandle,
-    ) -> Result<(), String> {
-        info!("Starting message handler");
-        
-        let stream_arc = {
-            let state = ws_state.lock().await;
-            state.stream.clone().ok_or("No WebSocket stream")?
-        };
+    async fn handle_messages(&self, mut source: WsSource) -> Result<(), String> {
+        info!("Starting message loop");
 
-        let mut stream_lock = stream_arc.lock().await;
-        info!("WebSocket stream lock acquired, starting message loop");
-
-        while let Some(message) = stream_lock.next().await {
+        while let Some(message) = source.next().await {
             match message {
                 Ok(Message::Text(text)) => {
-                    info!("Received text message: {}", text);
-                    
-                    if text == "registered" {
-                        info!("Registration confirmed by server");
-                        // Update registration status in state
-                        let mut state = ws_state.lock().await;
-                        state.is_registered = true;
-                        drop(state); // Release the lock immediately after update
-                        
-                        info!("Emitting websocket-status event - Connected: true, Registered: true");
-                        app_handle.emit("websocket-status", serde_json::json!({
-                            "connected": true,
-                            "registered": true
-                        })).map_err(|e| format!("Failed to emit event: {}", e))?;
-                    } else if text == "pong" {
-                        // Heartbeat response - log for debugging
-                        info!("Received heartbeat response (pong) from server");
-                    } else {
-                        info!("Forwarding text message to frontend");
-                        // Forward message to frontend
-                        app_handle.emit("websocket-message", text)
-                            .map_err(|e| format!("Failed to emit message: {}", e))?;
-                    }
+                    debug!("Received text message: {}", text);
+                    self.dispatch_server_message(ServerMessage::parse_text(&text)).await;
                 }
                 Ok(Message::Binary(data)) => {
-                    info!("Received binary data - Size: {} bytes", data.len());
-                    // Forward binary data (e.g., audio) to frontend
-                    app_handle.emit("websocket-binary", data)
-                        .map_err(|e| format!("Failed to emit binary data: {}", e))?;
+                    self.dispatch_server_message(Ok(ServerMessage::from_binary(data))).await;
                 }
                 Ok(Message::Close(_)) => {
                     info!("WebSocket closed by server");
@@ -280,50 +337,293 @@ impl WebSocketService {
             }
         }
 
-        info!("Message loop ended, updating WebSocket state");
-        
-        // Connection closed, update state
-        {
-            let mut state = ws_state.lock().await;
-            state.is_connected = false;
-            state.is_registered = false;
-            state.stream = None; // Stream was already taken, set to None for clarity
-            info!("WebSocket state updated - Connected: false, Registered: false");
-        }
-        
-        info!("Emitting websocket-status event - Connected: false, Registered: false");
-        app_handle.emit("websocket-status", serde_json::json!({
-            "connected": false,
-            "registered": false
-        })).map_err(|e| format!("Failed to emit event: {}", e))?;
-        
+        info!("Message loop ended");
         Ok(())
     }
-    
-    async fn ping_task(ws_state: Arc<Mutex<WebSocketState>>) -> Result<(), String> {
+
+    async fn writer_task(mut sink: WsSink, mut outbound: mpsc::Receiver<Message>) {
+        while let Some(message) = outbound.recv().await {
+            let is_close = matches!(message, Message::Close(_));
+            if let Err(e) = sink.send(message).await {
+                warn!("WebSocket write failed (server may be unavailable): {}", e);
+                break;
+            }
+            if is_close {
+                break;
+            }
+        }
+
+        let _ = sink.close().await;
+        info!("WebSocket writer finished");
+    }
+
+    async fn enqueue(&self, message: Message) -> Result<(), LilyError> {
+        // Clone the sender and release the state lock before awaiting queue capacity
+        let outbound = self.state.lock().await.outbound.clone()
+            .ok_or_else(|| LilyError::not_connected("WebSocket not connected"))?;
+        outbound.send(message).await
+            .map_err(|_| LilyError::network("WebSocket connection closed"))
+    }
+
+    async fn dispatch_server_message(&self, message: Result<ServerMessage, ProtocolError>) {
+        match message {
+            Ok(ServerMessage::Registered) => {
+                info!("Registration confirmed by server");
+                // Update registration status in state
+                self.transition(ConnectionState::Registered).await;
+                self.emit("registration", RegistrationEvent { registered: true });
+            }
+            Ok(ServerMessage::Pong) => {
+                // Heartbeat response - log for debugging
+                debug!("Received heartbeat response (pong) from server");
+            }
+            Ok(ServerMessage::Transcription(transcription)) => {
+                info!("Received {:?} transcription", transcription.kind);
+                self.emit("transcription", transcription);
+            }
+            Ok(ServerMessage::TtsAudio(data)) => {
+                info!("Received binary data - Size: {} bytes", data.len());
+                let played = match &self.playback {
+                    // Opening the output device blocks, so keep it off the reader's runtime thread
+                    Some(playback) => {
+                        let playback = playback.clone();
+                        let audio = data.clone();
+                        tokio::task::spawn_blocking(move || playback.enqueue(&audio).map(|_| ()))
+                            .await
+                            .unwrap_or_else(|e| Err(format!("playback task failed: {}", e)))
+                    }
+                    None => Err("no playback service".to_string()),
+                };
+                if let Err(e) = played {
+                    // No usable output device: let the webview play it instead
+                    warn!("Native TTS playback unavailable ({}), forwarding audio to frontend", e);
+                    self.emit("websocket-binary", data);
+                }
+            }
+            Ok(ServerMessage::Error(error)) => {
+                warn!("Server reported an error: {}", error.message);
+                self.emit("server-error", error);
+            }
+            Ok(ServerMessage::Unknown(text)) => {
+                warn!("Unrecognised server message, forwarding raw text: {}", text);
+                self.emit("websocket-message", text);
+            }
+            Err(e) => {
+                warn!("{}", e);
+                self.emit("server-error", ServerError {
+                    message: e.to_string(),
+                    code: Some("protocol".to_string()),
+                });
+            }
+        }
+    }
+
+    async fn ping_task(&self) -> Result<(), String> {
         let mut interval = interval(Duration::from_secs(25)); // Ping every 25 seconds
-        
+
         loop {
             interval.tick().await;
-            
+
             // Check if we're still connected
             {
-                let state = ws_state.lock().await;
-                if !state.is_connected {
+                let state = self.state.lock().await;
+                if !state.is_connected() {
                     info!("WebSocket disconnected, stopping ping task");
                     break;
                 }
             }
-            
+
             // Send ping message
             info!("Sending ping to server");
-            if let Err(e) = WebSocketService::send_message("ping".to_string(), ws_state.lock().await.app_handle.clone().unwrap()).await {
+            if let Err(e) = self.enqueue(Message::Text(ClientMessage::Ping.to_text())).await {
                 warn!("Failed to send ping (server may be unavailable): {}", e);
                 // Break the loop to trigger reconnection
                 break;
             }
         }
-        
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::error::ErrorKind;
+    use crate::domain::interfaces::MockFileStorageTrait;
+    use crate::domain::models::{AppSettings, ReconnectSettings};
+    use crate::infrastructure::protocol::{Transcription, TranscriptionKind};
+    use crate::services::events::{ChannelEventSink, EmittedEvent};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    fn service() -> (WebSocketService, UnboundedReceiver<EmittedEvent>) {
+        let (sink, rx) = ChannelEventSink::new();
+        (WebSocketService::new(Arc::new(sink)), rx)
+    }
+
+    async fn next_event(rx: &mut UnboundedReceiver<EmittedEvent>, name: &str) -> EmittedEvent {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let event = rx.recv().await.expect("event sink closed");
+                if event.name == name {
+                    return event;
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("timed out waiting for {}", name))
+    }
+
+    #[tokio::test]
+    async fn test_send_without_connection_fails() {
+        let (service, _rx) = service();
+        assert_eq!(service.send_message("ping".to_string()).await.unwrap_err().kind, ErrorKind::NotConnected);
+        assert_eq!(service.send_binary_data(vec![0; 4]).await.unwrap_err().kind, ErrorKind::NotConnected);
+        assert!(!service.get_status().await.connected);
+    }
+
+    #[tokio::test]
+    async fn test_server_messages_become_events() {
+        let (service, mut rx) = service();
+
+        let transcription = Transcription { kind: TranscriptionKind::Final, text: "hello".to_string() };
+        service.dispatch_server_message(Ok(ServerMessage::Transcription(transcription))).await;
+        service.dispatch_server_message(Ok(ServerMessage::TtsAudio(vec![1, 2, 3]))).await;
+        service.dispatch_server_message(Ok(ServerMessage::Unknown("new:1".to_string()))).await;
+
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.name, "transcription");
+        assert_eq!(event.payload, serde_json::json!({ "type": "final", "text": "hello" }));
+        // Without a playback service TTS audio goes to the frontend
+        assert_eq!(rx.try_recv().unwrap(), EmittedEvent { name: "websocket-binary".to_string(), payload: serde_json::json!([1, 2, 3]) });
+        assert_eq!(rx.try_recv().unwrap().payload, serde_json::json!("new:1"));
+    }
+
+    #[tokio::test]
+    async fn test_connects_and_registers_with_loopback_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let register = socket.next().await.unwrap().unwrap();
+            assert_eq!(register, Message::Text("register:default_user".to_string()));
+            socket.send(Message::Text("registered".to_string())).await.unwrap();
+            // The first heartbeat goes out as soon as the connection is up
+            loop {
+                let message = socket.next().await.unwrap().unwrap();
+                if message != Message::Text(ClientMessage::Ping.to_text()) {
+                    return message;
+                }
+            }
+        });
+
+        let (service, mut rx) = service();
+        let service = service.with_server_settings(ServerSettings {
+            websocket_url: format!("ws://{}", address),
+            reconnect: ReconnectSettings { max_attempts: Some(0), ..ReconnectSettings::default() },
+            ..ServerSettings::default()
+        });
+
+        service.connect().await.unwrap();
+        let registration = next_event(&mut rx, "registration").await;
+        assert_eq!(registration.payload, serde_json::json!({ "registered": true }));
+        assert!(service.get_status().await.registered);
+
+        service.send_message("hello".to_string()).await.unwrap();
+        assert_eq!(server.await.unwrap(), Message::Text("hello".to_string()));
+
+        service.disconnect().await.unwrap();
+        assert!(!service.get_status().await.connected);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_url_is_read_from_injected_storage() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let register = socket.next().await.unwrap().unwrap();
+            socket.send(Message::Text("registered".to_string())).await.unwrap();
+            register
+        });
+
+        let mut storage = MockFileStorageTrait::new();
+        storage.expect_load_settings().returning(move || {
+            let server = ServerSettings {
+                websocket_url: format!("ws://{}", address),
+                reconnect: ReconnectSettings { max_attempts: Some(0), ..ReconnectSettings::default() },
+                ..ServerSettings::default()
+            };
+            Ok(AppSettings { server, ..AppSettings::default() })
+        });
+        let (service, mut rx) = service();
+        let service = service.with_storage(Arc::new(storage));
+
+        service.connect().await.unwrap();
+        next_event(&mut rx, "registration").await;
+        assert_eq!(server.await.unwrap(), Message::Text("register:default_user".to_string()));
+        service.disconnect().await.unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_settings_fall_back_to_defaults() {
+        let mut storage = MockFileStorageTrait::new();
+        storage.expect_load_settings().times(1).returning(|| Err(LilyError::storage("settings.json is locked")));
+        let (service, _rx) = service();
+        let service = service.with_storage(Arc::new(storage));
+        assert_eq!(service.server_settings().websocket_url, ServerSettings::default().websocket_url);
+    }
+
+    struct FailingSink;
+
+    impl EventSink for FailingSink {
+        fn emit_value(&self, _event: &str, _payload: serde_json::Value) -> Result<(), String> {
+            Err("window is closing".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_emits_keep_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            socket.next().await.unwrap().unwrap();
+            // Every one of these makes the client emit an event, and every emit fails
+            for text in ["registered", "new:1", "error:boom"] {
+                socket.send(Message::Text(text.to_string())).await.unwrap();
+            }
+            socket.send(Message::Binary(vec![1, 2, 3])).await.unwrap();
+            loop {
+                let message = socket.next().await.unwrap().unwrap();
+                if message != Message::Text(ClientMessage::Ping.to_text()) {
+                    return message;
+                }
+            }
+        });
+
+        let service = WebSocketService::new(Arc::new(FailingSink)).with_server_settings(ServerSettings {
+            websocket_url: format!("ws://{}", address),
+            reconnect: ReconnectSettings { max_attempts: Some(0), ..ReconnectSettings::default() },
+            ..ServerSettings::default()
+        });
+        service.connect().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !service.get_status().await.registered {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("never registered");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(service.get_status().await.registered);
+        service.send_message("still here".to_string()).await.unwrap();
+        assert_eq!(server.await.unwrap(), Message::Text("still here".to_string()));
+        service.disconnect().await.unwrap();
+    }
+}
@@ -0,0 +1,237 @@
+use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Notify};
+
+/// Number of samples in one fixed-duration frame.
+pub fn samples_per_frame(sample_rate: u32, frame_ms: u32) -> usize {
+    ((sample_rate as u64 * frame_ms as u64) / 1000).max(1) as usize
+}
+
+/// Counters shared between the capture callback and the frame sender.
+#[derive(Default)]
+pub struct FrameStats {
+    frames_sent: AtomicU64,
+    send_failures: AtomicU64,
+    samples_dropped: AtomicU64,
+    frames_skipped: AtomicU64,
+    frames_echo_suppressed: AtomicU64,
+}
+
+impl FrameStats {
+    pub fn record_sent(&self) {
+        self.frames_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_send_failure(&self) {
+        self.send_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, samples: usize) {
+        self.samples_dropped.fetch_add(samples as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_skipped(&self, frames: usize) {
+        self.frames_skipped.fetch_add(frames as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_echo_suppressed(&self) {
+        self.frames_echo_suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> FrameStatsSnapshot {
+        FrameStatsSnapshot {
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            send_failures: self.send_failures.load(Ordering::Relaxed),
+            samples_dropped: self.samples_dropped.load(Ordering::Relaxed),
+            frames_skipped: self.frames_skipped.load(Ordering::Relaxed),
+            frames_echo_suppressed: self.frames_echo_suppressed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStatsSnapshot {
+    pub frames_sent: u64,
+    pub send_failures: u64,
+    /// Samples discarded because the ring buffer was full (sender fell behind).
+    pub samples_dropped: u64,
+    /// Silent frames not streamed because voiced-only mode was on.
+    pub frames_skipped: u64,
+    /// Frames attenuated because they matched TTS playback.
+    pub frames_echo_suppressed: u64,
+}
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'a>>;
+
+/// Where encoded capture frames go: the Lily-Core socket in the app, a
+/// channel in tests. Frames are sent one at a time, in order.
+pub trait FrameOutput: Send + Sync {
+    fn send_frame(&self, frame: Vec<u8>) -> SendFuture<'_>;
+}
+
+// A bounded channel applies the same backpressure as a slow socket
+impl FrameOutput for mpsc::Sender<Vec<u8>> {
+    fn send_frame(&self, frame: Vec<u8>) -> SendFuture<'_> {
+        Box::pin(async move {
+            self.send(frame).await.map_err(|_| "Frame receiver was dropped".to_string())
+        })
+    }
+}
+
+/// Creates the two halves of the capture ring buffer. The writer lives in the
+/// real-time audio callback and never blocks; the reader hands out whole frames.
+pub fn frame_channel(
+    capacity: usize,
+    frame_len: usize,
+    stats: Arc<FrameStats>,
+) -> (FrameWriter, FrameReader) {
+    let (producer, consumer) = HeapRb::<f32>::new(capacity.max(frame_len)).split();
+    let notify = Arc::new(Notify::new());
+    let closed = Arc::new(AtomicBool::new(false));
+
+    let writer = FrameWriter {
+        producer,
+        stats,
+        notify: notify.clone(),
+        closed: closed.clone(),
+    };
+    let reader = FrameReader {
+        consumer,
+        frame_len,
+        notify,
+        closed,
+    };
+    (writer, reader)
+}
+
+pub struct FrameWriter {
+    producer: HeapProducer<f32>,
+    stats: Arc<FrameStats>,
+    notify: Arc<Notify>,
+    closed: Arc<AtomicBool>,
+}
+
+impl FrameWriter {
+    /// Queues samples, counting whatever doesn't fit as dropped.
+    pub fn write(&mut self, samples: &[f32]) {
+        let pushed = self.producer.push_slice(samples);
+        if pushed < samples.len() {
+            self.stats.record_dropped(samples.len() - pushed);
+        }
+        self.notify.notify_one();
+    }
+}
+
+impl Drop for FrameWriter {
+    // The stream (and with it the callback owning this writer) is gone; let the reader flush
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Release);
+        self.notify.notify_one();
+    }
+}
+
+pub struct FrameReader {
+    consumer: HeapConsumer<f32>,
+    frame_len: usize,
+    notify: Arc<Notify>,
+    closed: Arc<AtomicBool>,
+}
+
+impl FrameReader {
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    /// Pops exactly one frame if enough samples are buffered.
+    pub fn next_frame(&mut self) -> Option<Vec<f32>> {
+        if self.consumer.len() < self.frame_len {
+            return None;
+        }
+        let mut frame = vec![0.0; self.frame_len];
+        self.consumer.pop_slice(&mut frame);
+        Some(frame)
+    }
+
+    /// Drains the trailing partial frame once the writer has gone away.
+    pub fn flush(&mut self) -> Option<Vec<f32>> {
+        let remaining = self.consumer.len();
+        if remaining == 0 {
+            return None;
+        }
+        let mut frame = vec![0.0; remaining];
+        self.consumer.pop_slice(&mut frame);
+        Some(frame)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Waits until the writer has pushed more samples or closed.
+    pub async fn wait(&self) {
+        if !self.is_closed() {
+            self.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_per_frame() {
+        assert_eq!(samples_per_frame(16_000, 20), 320);
+        assert_eq!(samples_per_frame(16_000, 40), 640);
+        assert_eq!(samples_per_frame(48_000, 100), 4800);
+        assert_eq!(samples_per_frame(16_000, 0), 1);
+    }
+
+    #[test]
+    fn test_reader_emits_fixed_size_frames_in_order() {
+        let stats = Arc::new(FrameStats::default());
+        let (mut writer, mut reader) = frame_channel(64, 4, stats);
+
+        writer.write(&[1.0, 2.0, 3.0]);
+        assert_eq!(reader.next_frame(), None);
+
+        writer.write(&[4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+        assert_eq!(reader.next_frame(), Some(vec![1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(reader.next_frame(), Some(vec![5.0, 6.0, 7.0, 8.0]));
+        assert_eq!(reader.next_frame(), None);
+        assert_eq!(reader.flush(), Some(vec![9.0]));
+        assert_eq!(reader.flush(), None);
+    }
+
+    #[test]
+    fn test_overflow_is_counted_as_dropped() {
+        let stats = Arc::new(FrameStats::default());
+        let (mut writer, mut reader) = frame_channel(8, 4, stats.clone());
+
+        writer.write(&[0.0; 6]);
+        writer.write(&[0.0; 6]);
+        assert_eq!(stats.snapshot().samples_dropped, 4);
+
+        assert!(reader.next_frame().is_some());
+        assert!(reader.next_frame().is_some());
+        assert!(reader.next_frame().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dropping_writer_closes_reader() {
+        let stats = Arc::new(FrameStats::default());
+        let (mut writer, mut reader) = frame_channel(16, 4, stats);
+
+        writer.write(&[0.5, 0.5]);
+        drop(writer);
+
+        // Must not hang: the close notification is stored as a permit
+        reader.wait().await;
+        assert!(reader.is_closed());
+        assert_eq!(reader.flush(), Some(vec![0.5, 0.5]));
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+pub const SILENCE_DBFS: f32 = -100.0;
+// Samples at or above this magnitude count as clipped (int formats never quite reach 1.0)
+const CLIP_LEVEL: f32 = 0.999;
+const PEAK_HOLD: Duration = Duration::from_millis(1500);
+// Keep the clip indicator lit long enough for a polling UI to notice it
+const CLIP_HOLD: Duration = Duration::from_millis(1000);
+
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum_squares: f32 = samples.iter().map(|sample| sample * sample).sum();
+    (sum_squares / samples.len() as f32).sqrt()
+}
+
+pub fn to_dbfs(level: f32) -> f32 {
+    if level <= 0.0 {
+        SILENCE_DBFS
+    } else {
+        (20.0 * level.log10()).max(SILENCE_DBFS)
+    }
+}
+
+/// Payload of `get_audio_meter`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct AudioMeterReading {
+    pub rms: f32,
+    pub peak: f32,
+    /// Highest peak over the last hold window.
+    pub peak_hold: f32,
+    pub dbfs: f32,
+    pub clipping: bool,
+}
+
+impl Default for AudioMeterReading {
+    fn default() -> Self {
+        Self {
+            rms: 0.0,
+            peak: 0.0,
+            peak_hold: 0.0,
+            dbfs: SILENCE_DBFS,
+            clipping: false,
+        }
+    }
+}
+
+/// Rolling level state updated from the capture callback.
+#[derive(Default)]
+pub struct AudioMeter {
+    reading: AudioMeterReading,
+    peak_hold_at: Option<Instant>,
+    clipped_at: Option<Instant>,
+}
+
+impl AudioMeter {
+    pub fn update(&mut self, samples: &[f32]) {
+        self.update_at(samples, Instant::now());
+    }
+
+    pub fn update_at(&mut self, samples: &[f32], now: Instant) {
+        if samples.is_empty() {
+            return;
+        }
+
+        let rms = rms(samples);
+        let peak = samples.iter().fold(0.0f32, |max, sample| max.max(sample.abs()));
+
+        let hold_expired = self.peak_hold_at.is_none_or(|at| now.duration_since(at) >= PEAK_HOLD);
+        if peak >= self.reading.peak_hold || hold_expired {
+            self.reading.peak_hold = peak;
+            self.peak_hold_at = Some(now);
+        }
+
+        if peak >= CLIP_LEVEL {
+            self.clipped_at = Some(now);
+        }
+
+        self.reading.rms = rms;
+        self.reading.peak = peak;
+        self.reading.dbfs = to_dbfs(rms);
+        self.reading.clipping = self.clipped_at.is_some_and(|at| now.duration_since(at) < CLIP_HOLD);
+    }
+
+    pub fn reading(&self) -> AudioMeterReading {
+        self.reading
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rms_and_dbfs() {
+        assert_eq!(rms(&[]), 0.0);
+        assert!((rms(&[0.5, -0.5, 0.5, -0.5]) - 0.5).abs() < 1e-6);
+        assert_eq!(to_dbfs(0.0), SILENCE_DBFS);
+        assert!(to_dbfs(1.0).abs() < 1e-4);
+        assert!((to_dbfs(0.1) + 20.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_meter_tracks_level_and_peak() {
+        let mut meter = AudioMeter::default();
+        assert_eq!(meter.reading(), AudioMeterReading::default());
+
+        meter.update_at(&[0.5, -0.5, 0.25, -0.25], Instant::now());
+        let reading = meter.reading();
+        assert_eq!(reading.peak, 0.5);
+        assert_eq!(reading.peak_hold, 0.5);
+        assert!((reading.rms - 0.3953).abs() < 1e-3);
+        assert!((reading.dbfs - to_dbfs(reading.rms)).abs() < 1e-6);
+        assert!(!reading.clipping);
+    }
+
+    #[test]
+    fn test_peak_hold_expires() {
+        let mut meter = AudioMeter::default();
+        let start = Instant::now();
+
+        meter.update_at(&[0.8], start);
+        meter.update_at(&[0.1], start + Duration::from_millis(500));
+        assert_eq!(meter.reading().peak, 0.1);
+        assert_eq!(meter.reading().peak_hold, 0.8);
+
+        meter.update_at(&[0.1], start + PEAK_HOLD);
+        assert_eq!(meter.reading().peak_hold, 0.1);
+    }
+
+    #[test]
+    fn test_clipping_is_held_briefly() {
+        let mut meter = AudioMeter::default();
+        let start = Instant::now();
+
+        meter.update_at(&[0.2, 1.0], start);
+        assert!(meter.reading().clipping);
+
+        meter.update_at(&[0.2], start + Duration::from_millis(200));
+        assert!(meter.reading().clipping);
+
+        meter.update_at(&[0.2], start + CLIP_HOLD);
+        assert!(!meter.reading().clipping);
+
+        meter.reset();
+        assert_eq!(meter.reading(), AudioMeterReading::default());
+    }
+}
@@ -0,0 +1,421 @@
+use crate::domain::error::LilyError;
+use crate::services::audio_frames::samples_per_frame;
+use serde::{Deserialize, Serialize};
+
+/// Sample encoding of the binary frames streamed to Lily-Core.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamEncoding {
+    /// Signed 16-bit little-endian PCM.
+    Pcm16,
+    /// 32-bit little-endian IEEE float.
+    F32,
+}
+
+impl StreamEncoding {
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            StreamEncoding::Pcm16 => 2,
+            StreamEncoding::F32 => 4,
+        }
+    }
+}
+
+// Target format for microphone audio, persisted under `AppSettings.audio.stream`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct StreamSettings {
+    pub sample_rate: u32,
+    pub encoding: StreamEncoding,
+    /// Duration of each binary frame sent to the server.
+    pub frame_ms: u32,
+}
+
+const MIN_FRAME_MS: u32 = 10;
+const MAX_FRAME_MS: u32 = 200;
+
+// Anti-alias cutoff as a fraction of the output rate, a little below Nyquist
+const ANTI_ALIAS_CUTOFF: f64 = 0.45;
+// Q of the two sections of a 4th-order Butterworth low-pass
+const BUTTERWORTH_Q: [f64; 2] = [0.541_196_1, 1.306_563];
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            encoding: StreamEncoding::Pcm16,
+            frame_ms: 40,
+        }
+    }
+}
+
+impl StreamSettings {
+    pub fn validate(&self) -> Result<(), LilyError> {
+        if self.sample_rate == 0 {
+            return Err(LilyError::validation("Stream sample rate must be greater than zero"));
+        }
+        Ok(())
+    }
+
+    pub fn frame_ms(&self) -> u32 {
+        self.frame_ms.clamp(MIN_FRAME_MS, MAX_FRAME_MS)
+    }
+
+    /// Samples per outgoing frame at the target rate.
+    pub fn frame_len(&self) -> usize {
+        samples_per_frame(self.sample_rate, self.frame_ms())
+    }
+
+    pub fn format(&self) -> StreamFormat {
+        StreamFormat {
+            encoding: self.encoding,
+            sample_rate: self.sample_rate,
+            channels: 1,
+        }
+    }
+}
+
+/// What the server should expect on the wire; sent as the audio handshake.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct StreamFormat {
+    pub encoding: StreamEncoding,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Averages interleaved frames down to a single channel.
+pub fn downmix_to_mono(interleaved: &[f32], channels: u16) -> Vec<f32> {
+    let mut mono = Vec::with_capacity(interleaved.len() / channels.max(1) as usize);
+    downmix_into(interleaved, channels, &mut mono);
+    mono
+}
+
+/// Like `downmix_to_mono`, appending to `output` so callers can reuse a buffer.
+pub fn downmix_into(interleaved: &[f32], channels: u16, output: &mut Vec<f32>) {
+    match channels {
+        0 | 1 => output.extend_from_slice(interleaved),
+        n => output.extend(
+            interleaved
+                .chunks_exact(n as usize)
+                .map(|frame| frame.iter().sum::<f32>() / n as f32),
+        ),
+    }
+}
+
+// Second-order low-pass section (RBJ audio EQ cookbook), state kept between calls
+#[derive(Clone, Copy)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Biquad {
+    fn low_pass(sample_rate: f64, cutoff: f64, q: f64) -> Self {
+        let w0 = 2.0 * std::f64::consts::PI * cutoff / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 - cos) / 2.0 / a0,
+            b1: (1.0 - cos) / a0,
+            b2: (1.0 - cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Streaming linear-interpolation resampler. Keeps the fractional read
+/// position and last input sample between calls so chunk boundaries from
+/// the audio callback don't introduce clicks or drift. When downsampling,
+/// input is first low-passed below the new Nyquist frequency so content
+/// above it doesn't fold back into the speech band.
+pub struct LinearResampler {
+    step: f64,
+    position: f64,
+    previous: f32,
+    anti_alias: Option<[Biquad; 2]>,
+    // Filtered input, reused between calls
+    filtered: Vec<f32>,
+}
+
+impl LinearResampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let (input_rate, output_rate) = (input_rate.max(1), output_rate.max(1));
+        let anti_alias = (output_rate < input_rate).then(|| {
+            let cutoff = output_rate as f64 * ANTI_ALIAS_CUTOFF;
+            BUTTERWORTH_Q.map(|q| Biquad::low_pass(input_rate as f64, cutoff, q))
+        });
+        Self {
+            step: input_rate as f64 / output_rate as f64,
+            // Index 0 of the virtual buffer is `previous`; start on the first real sample
+            position: 1.0,
+            previous: 0.0,
+            anti_alias,
+            filtered: Vec::new(),
+        }
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        self.step == 1.0
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        let mut output = Vec::with_capacity((input.len() as f64 / self.step).ceil() as usize + 1);
+        self.process_into(input, &mut output);
+        output
+    }
+
+    /// Like `process`, appending to `output` so callers can reuse a buffer.
+    pub fn process_into(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        if self.is_passthrough() || input.is_empty() {
+            output.extend_from_slice(input);
+            return;
+        }
+
+        let mut filtered = std::mem::take(&mut self.filtered);
+        if let Some(sections) = self.anti_alias.as_mut() {
+            filtered.clear();
+            filtered.extend(input.iter().map(|&sample| {
+                sections.iter_mut().fold(sample as f64, |x, section| section.process(x)) as f32
+            }));
+        }
+        let input = if self.anti_alias.is_some() { &filtered[..] } else { input };
+        self.interpolate(input, output);
+        self.filtered = filtered;
+    }
+
+    fn interpolate(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let len = input.len() as f64;
+        while self.position < len {
+            let index = self.position.floor() as usize;
+            let fraction = (self.position - index as f64) as f32;
+            let a = if index == 0 { self.previous } else { input[index - 1] };
+            let b = input[index];
+            output.push(a + (b - a) * fraction);
+            self.position += self.step;
+        }
+
+        self.position -= len;
+        self.previous = input[input.len() - 1];
+    }
+}
+
+/// Converts mono samples to wire bytes in the requested encoding.
+pub fn encode_samples(samples: &[f32], encoding: StreamEncoding) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(samples.len() * encoding.bytes_per_sample());
+    match encoding {
+        StreamEncoding::Pcm16 => {
+            for &sample in samples {
+                let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        StreamEncoding::F32 => {
+            for &sample in samples {
+                bytes.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+/// Capture-side conversion: device frames in, Lily-Core wire bytes out.
+pub struct AudioPipeline {
+    channels: u16,
+    resampler: LinearResampler,
+    settings: StreamSettings,
+    // Downmixed input, kept between calls so steady-state processing doesn't allocate
+    mono: Vec<f32>,
+}
+
+impl AudioPipeline {
+    pub fn new(input_channels: u16, input_rate: u32, settings: StreamSettings) -> Self {
+        Self {
+            channels: input_channels,
+            resampler: LinearResampler::new(input_rate, settings.sample_rate),
+            settings,
+            mono: Vec::new(),
+        }
+    }
+
+    pub fn format(&self) -> StreamFormat {
+        self.settings.format()
+    }
+
+    /// Downmixes and resamples, returning mono samples at the target rate.
+    pub fn process_samples(&mut self, interleaved: &[f32]) -> Vec<f32> {
+        let mut output = Vec::new();
+        self.process_into(interleaved, &mut output);
+        output
+    }
+
+    /// Like `process_samples`, replacing the contents of `output`. Once both
+    /// buffers have grown to the callback size this no longer allocates.
+    pub fn process_into(&mut self, interleaved: &[f32], output: &mut Vec<f32>) {
+        self.mono.clear();
+        downmix_into(interleaved, self.channels, &mut self.mono);
+        output.clear();
+        self.resampler.process_into(&self.mono, output);
+    }
+
+    pub fn process(&mut self, interleaved: &[f32]) -> Vec<u8> {
+        let samples = self.process_samples(interleaved);
+        encode_samples(&samples, self.settings.encoding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::error::ErrorKind;
+    use crate::services::audio_meter::rms;
+
+    #[test]
+    fn test_downmix_stereo_to_mono() {
+        let stereo = vec![1.0, 0.0, 0.5, 0.5, -1.0, 1.0];
+        assert_eq!(downmix_to_mono(&stereo, 2), vec![0.5, 0.5, 0.0]);
+        assert_eq!(downmix_to_mono(&stereo, 1), stereo);
+    }
+
+    #[test]
+    fn test_resampler_output_length_across_chunks() {
+        let mut resampler = LinearResampler::new(48_000, 16_000);
+        let chunk = vec![0.0f32; 480]; // 10 ms at 48 kHz
+
+        let total: usize = (0..100).map(|_| resampler.process(&chunk).len()).sum();
+        assert_eq!(total, 16_000);
+    }
+
+    #[test]
+    fn test_resampler_handles_uneven_chunks() {
+        let mut resampler = LinearResampler::new(44_100, 16_000);
+        let mut total = 0;
+        for size in [441usize, 512, 97, 1024, 326].iter().cycle().take(200) {
+            total += resampler.process(&vec![0.0; *size]).len();
+        }
+        let input: usize = [441usize, 512, 97, 1024, 326].iter().sum::<usize>() * 40;
+        let expected = input as f64 * 16_000.0 / 44_100.0;
+        assert!((total as f64 - expected).abs() <= 1.0);
+    }
+
+    #[test]
+    fn test_resampler_interpolates_linear_ramp() {
+        // A ramp stays a ramp after resampling, including across the chunk boundary
+        let mut resampler = LinearResampler::new(1, 2);
+        let ramp: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let mut output = resampler.process(&ramp[..5]);
+        output.extend(resampler.process(&ramp[5..]));
+        assert_eq!(output, (0..14).map(|i| i as f32 * 0.5).collect::<Vec<_>>());
+    }
+
+    fn tone(frequency: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_downsampling_filters_out_aliases() {
+        // 1 s in 10 ms chunks, measured after the filter has settled
+        let level = |frequency: f32| {
+            let mut resampler = LinearResampler::new(48_000, 16_000);
+            let output: Vec<f32> = tone(frequency, 48_000, 48_000).chunks(480).flat_map(|chunk| resampler.process(chunk)).collect();
+            rms(&output[1_600..])
+        };
+        let input_level = 0.5 / 2f32.sqrt();
+
+        // Speech band passes; 12 kHz would otherwise fold back to 4 kHz
+        assert!((level(1_000.0) - input_level).abs() < 0.02 * input_level);
+        assert!(level(12_000.0) < 0.1 * input_level);
+    }
+
+    #[test]
+    fn test_zero_sample_rate_is_rejected() {
+        let settings = StreamSettings { sample_rate: 0, ..StreamSettings::default() };
+        assert_eq!(settings.validate().unwrap_err().kind, ErrorKind::Validation);
+        assert!(StreamSettings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_resampler_passthrough_at_same_rate() {
+        let mut resampler = LinearResampler::new(16_000, 16_000);
+        assert!(resampler.is_passthrough());
+        assert_eq!(resampler.process(&[0.1, 0.2]), vec![0.1, 0.2]);
+    }
+
+    #[test]
+    fn test_pipeline_reuses_output_buffer() {
+        let mut pipeline = AudioPipeline::new(2, 48_000, StreamSettings::default());
+        let mut output = Vec::new();
+        pipeline.process_into(&[0.5; 960], &mut output);
+        assert_eq!(output.len(), 160);
+
+        let (pointer, capacity) = (output.as_ptr(), output.capacity());
+        pipeline.process_into(&[0.5; 960], &mut output);
+        assert_eq!(output.len(), 160);
+        assert_eq!((output.as_ptr(), output.capacity()), (pointer, capacity));
+    }
+
+    #[test]
+    fn test_encode_pcm16_clamps_and_scales() {
+        let bytes = encode_samples(&[0.0, 1.0, -1.0, 2.0], StreamEncoding::Pcm16);
+        let values: Vec<i16> = bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect();
+        assert_eq!(values, vec![0, i16::MAX, -i16::MAX, i16::MAX]);
+    }
+
+    #[test]
+    fn test_encode_f32_round_trips() {
+        let bytes = encode_samples(&[0.25, -0.5], StreamEncoding::F32);
+        let values: Vec<f32> = bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        assert_eq!(values, vec![0.25, -0.5]);
+    }
+
+    #[test]
+    fn test_frame_len_is_clamped() {
+        let mut settings = StreamSettings::default();
+        assert_eq!(settings.frame_len(), 640);
+
+        settings.frame_ms = 20;
+        assert_eq!(settings.frame_len(), 320);
+
+        settings.frame_ms = 0;
+        assert_eq!(settings.frame_ms(), MIN_FRAME_MS);
+        settings.frame_ms = 5_000;
+        assert_eq!(settings.frame_ms(), MAX_FRAME_MS);
+    }
+
+    #[test]
+    fn test_pipeline_stereo_48k_to_pcm16_16k() {
+        let mut pipeline = AudioPipeline::new(2, 48_000, StreamSettings::default());
+        let frames = vec![0.5f32; 960]; // 480 stereo frames = 10 ms
+        let bytes = pipeline.process(&frames);
+
+        assert_eq!(bytes.len(), 160 * 2);
+        assert_eq!(pipeline.format(), StreamFormat {
+            encoding: StreamEncoding::Pcm16,
+            sample_rate: 16_000,
+            channels: 1,
+        });
+    }
+}
@@ -0,0 +1,884 @@
+use crate::services::audio_pipeline::{downmix_to_mono, LinearResampler};
+use crate::services::echo::EchoReference;
+use crate::services::wav::{decode_samples, decode_wav, WAVE_FORMAT_PCM};
+pub use crate::services::wav::DecodedAudio;
+use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+#[cfg(feature = "audio")]
+use crate::services::audio_service::match_device_name;
+#[cfg(feature = "audio")]
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+#[cfg(feature = "audio")]
+use std::sync::mpsc;
+#[cfg(feature = "audio")]
+use std::thread;
+#[cfg(feature = "audio")]
+use std::time::Duration;
+use crate::services::events::{EventSink, NullEventSink};
+
+// Speaker output the callback can get ahead of the worker thread by, in ms
+const REFERENCE_BACKLOG_MS: u32 = 500;
+const EVENT_BACKLOG: usize = 64;
+// How often the playback thread forwards what the callback played
+#[cfg(feature = "audio")]
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Format assumed for headerless PCM16 audio from Lily-Core.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PcmFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl Default for PcmFormat {
+    fn default() -> Self {
+        // Matches the default `TTSParameters.sample_rate`
+        Self {
+            sample_rate: 24_000,
+            channels: 1,
+        }
+    }
+}
+
+/// Decodes a TTS reply: a RIFF/WAVE file when it has a header, otherwise raw
+/// little-endian PCM16 in `raw_format`.
+pub fn decode_tts_audio(data: &[u8], raw_format: PcmFormat) -> Result<DecodedAudio, String> {
+    if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+        return decode_wav(data);
+    }
+
+    Ok(DecodedAudio {
+        samples: decode_samples(data, WAVE_FORMAT_PCM, 16)?,
+        sample_rate: raw_format.sample_rate,
+        channels: raw_format.channels.max(1),
+    })
+}
+
+/// Converts decoded audio to the output device's channel count and rate.
+pub fn convert_for_output(audio: &DecodedAudio, channels: u16, sample_rate: u32) -> Vec<f32> {
+    // TTS is speech: mono is enough, and it upmixes to any layout
+    let mut mono = downmix_to_mono(&audio.samples, audio.channels);
+    // The streaming resampler lags one input sample; repeat the last so a whole clip comes out
+    if let Some(&last) = mono.last() {
+        mono.push(last);
+    }
+    let resampled = LinearResampler::new(audio.sample_rate, sample_rate).process(&mono);
+
+    let channels = channels.max(1) as usize;
+    let mut output = Vec::with_capacity(resampled.len() * channels);
+    for sample in resampled {
+        output.extend(std::iter::repeat_n(sample, channels));
+    }
+    output
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Completed,
+    Skipped,
+    Stopped,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlaybackEvent {
+    Started { id: u64, duration_ms: u64 },
+    Finished { id: u64, reason: FinishReason },
+}
+
+/// Payload of `get_tts_playback_status`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PlaybackStatus {
+    pub playing: Option<u64>,
+    pub paused: bool,
+    pub queued: usize,
+    pub volume: f32,
+    /// Temporarily lowered because the user is talking over playback.
+    pub ducked: bool,
+}
+
+struct Clip {
+    id: u64,
+    samples: Vec<f32>,
+    position: usize,
+    duration_ms: u64,
+}
+
+/// FIFO of converted clips, drained by the output callback.
+pub struct PlaybackQueue {
+    clips: VecDeque<Clip>,
+    current: Option<Clip>,
+    paused: bool,
+    volume: f32,
+    duck_gain: f32,
+    next_id: u64,
+}
+
+impl Default for PlaybackQueue {
+    fn default() -> Self {
+        Self {
+            clips: VecDeque::new(),
+            current: None,
+            paused: false,
+            volume: 1.0,
+            duck_gain: 1.0,
+            next_id: 1,
+        }
+    }
+}
+
+impl PlaybackQueue {
+    /// Queues samples already in the output format; returns the clip id.
+    pub fn push(&mut self, samples: Vec<f32>, duration_ms: u64) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.clips.push_back(Clip {
+            id,
+            samples,
+            position: 0,
+            duration_ms,
+        });
+        id
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Extra gain on top of the user's volume; `None` restores full level.
+    pub fn set_duck(&mut self, gain: Option<f32>) {
+        self.duck_gain = gain.map_or(1.0, |gain| gain.clamp(0.0, 1.0));
+    }
+
+    /// Drops the current clip; the next one starts on the following callback.
+    pub fn skip(&mut self) -> Option<PlaybackEvent> {
+        self.current.take().map(|clip| PlaybackEvent::Finished {
+            id: clip.id,
+            reason: FinishReason::Skipped,
+        })
+    }
+
+    /// Drops the current clip and everything queued behind it.
+    pub fn stop(&mut self) -> Option<PlaybackEvent> {
+        self.clips.clear();
+        self.paused = false;
+        self.current.take().map(|clip| PlaybackEvent::Finished {
+            id: clip.id,
+            reason: FinishReason::Stopped,
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.current.is_some() || !self.clips.is_empty()
+    }
+
+    pub fn status(&self) -> PlaybackStatus {
+        PlaybackStatus {
+            playing: self.current.as_ref().map(|clip| clip.id),
+            paused: self.paused,
+            queued: self.clips.len(),
+            volume: self.volume,
+            ducked: self.duck_gain < 1.0,
+        }
+    }
+
+    /// Fills an interleaved output buffer, starting queued clips as the
+    /// previous one runs out. Silence when paused or idle.
+    pub fn fill(&mut self, output: &mut [f32], events: &mut Vec<PlaybackEvent>) {
+        let mut written = 0;
+
+        while !self.paused && written < output.len() {
+            if self.current.is_none() {
+                let Some(clip) = self.clips.pop_front() else {
+                    break;
+                };
+                events.push(PlaybackEvent::Started {
+                    id: clip.id,
+                    duration_ms: clip.duration_ms,
+                });
+                self.current = Some(clip);
+            }
+
+            let gain = self.volume * self.duck_gain;
+            let clip = self.current.as_mut().unwrap();
+            let count = (clip.samples.len() - clip.position).min(output.len() - written);
+            for (out, &sample) in output[written..written + count]
+                .iter_mut()
+                .zip(&clip.samples[clip.position..clip.position + count])
+            {
+                *out = sample * gain;
+            }
+            clip.position += count;
+            written += count;
+
+            if clip.position >= clip.samples.len() {
+                events.push(PlaybackEvent::Finished {
+                    id: clip.id,
+                    reason: FinishReason::Completed,
+                });
+                self.current = None;
+            }
+        }
+
+        output[written..].fill(0.0);
+    }
+}
+
+// Fans playback events out to in-process subscribers and the frontend
+#[derive(Clone)]
+struct PlaybackNotifier {
+    events_tx: broadcast::Sender<PlaybackEvent>,
+    sink: Arc<Mutex<Arc<dyn EventSink>>>,
+}
+
+impl PlaybackNotifier {
+    fn notify(&self, event: PlaybackEvent) {
+        let _ = self.events_tx.send(event);
+
+        let name = match event {
+            PlaybackEvent::Started { .. } => "tts-playback-started",
+            PlaybackEvent::Finished { .. } => "tts-playback-finished",
+        };
+        let sink = self.sink.lock().unwrap().clone();
+        let _ = sink.emit(name, event);
+    }
+}
+
+// Output device format the queue is converted to
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+#[derive(Clone, Copy, Debug)]
+struct OutputFormat {
+    channels: u16,
+    sample_rate: u32,
+}
+
+// Real-time half of the output callback. It never waits on a lock or
+// allocates once warmed up: what it plays goes to the playback thread through
+// lock-free rings.
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+struct OutputRenderer {
+    queue: Arc<Mutex<PlaybackQueue>>,
+    channels: usize,
+    buffer: Vec<f32>,
+    events: Vec<PlaybackEvent>,
+    reference_tx: HeapProducer<f32>,
+    events_tx: HeapProducer<PlaybackEvent>,
+}
+
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+impl OutputRenderer {
+    // Interleaved samples for the next `len` output slots
+    fn render(&mut self, len: usize) -> &[f32] {
+        self.buffer.resize(len, 0.0);
+        // Never block the audio thread on a control call; play silence instead
+        match self.queue.try_lock() {
+            Ok(mut queue) => queue.fill(&mut self.buffer, &mut self.events),
+            Err(_) => self.buffer.fill(0.0),
+        }
+
+        // Everything played (silence too, to stay time-aligned) is the echo reference
+        for frame in self.buffer.chunks_exact(self.channels) {
+            let _ = self.reference_tx.push(frame.iter().sum::<f32>() / self.channels as f32);
+        }
+        for event in self.events.drain(..) {
+            let _ = self.events_tx.push(event);
+        }
+        &self.buffer
+    }
+}
+
+// The playback thread's half: feeds the echo reference and sends events
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+struct OutputDrain {
+    sample_rate: u32,
+    scratch: Vec<f32>,
+    reference_rx: HeapConsumer<f32>,
+    events_rx: HeapConsumer<PlaybackEvent>,
+    echo_reference: EchoReference,
+    notifier: PlaybackNotifier,
+}
+
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+impl OutputDrain {
+    fn drain(&mut self) {
+        loop {
+            let count = self.reference_rx.pop_slice(&mut self.scratch);
+            if count == 0 {
+                break;
+            }
+            self.echo_reference.write(&self.scratch[..count], self.sample_rate);
+        }
+        while let Some(event) = self.events_rx.pop() {
+            self.notifier.notify(event);
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+fn output_pipeline(
+    format: OutputFormat,
+    queue: Arc<Mutex<PlaybackQueue>>,
+    notifier: PlaybackNotifier,
+    echo_reference: EchoReference,
+) -> (OutputRenderer, OutputDrain) {
+    let backlog = (format.sample_rate as u64 * REFERENCE_BACKLOG_MS as u64 / 1000).max(1) as usize;
+    let (reference_tx, reference_rx) = HeapRb::<f32>::new(backlog).split();
+    let (events_tx, events_rx) = HeapRb::<PlaybackEvent>::new(EVENT_BACKLOG).split();
+    let renderer = OutputRenderer {
+        queue,
+        channels: format.channels.max(1) as usize,
+        buffer: Vec::new(),
+        events: Vec::with_capacity(EVENT_BACKLOG),
+        reference_tx,
+        events_tx,
+    };
+    let drain = OutputDrain {
+        sample_rate: format.sample_rate,
+        scratch: vec![0.0; backlog],
+        reference_rx,
+        events_rx,
+        echo_reference,
+        notifier,
+    };
+    (renderer, drain)
+}
+
+// An open output: the stream (only held to keep the device open), its format
+// and the drain for what the callback played
+#[cfg(feature = "audio")]
+type OpenedOutput = (Box<dyn std::any::Any>, OutputFormat, OutputDrain);
+
+// Opens an output device on the playback thread; `open_output_stream` outside tests
+#[cfg(feature = "audio")]
+type OpenOutput = Arc<
+    dyn Fn(Option<&str>, Arc<Mutex<PlaybackQueue>>, PlaybackNotifier, EchoReference) -> Result<OpenedOutput, String>
+        + Send
+        + Sync,
+>;
+
+// Thread that owns the (non-`Send`) output stream. Dropping it closes the
+// shutdown channel, which ends the thread and releases the device.
+#[cfg(feature = "audio")]
+struct PlaybackWorker {
+    shutdown: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+    format: OutputFormat,
+    device_name: Option<String>,
+}
+
+#[cfg(feature = "audio")]
+impl PlaybackWorker {
+    fn spawn(
+        open: OpenOutput,
+        device_name: Option<String>,
+        queue: Arc<Mutex<PlaybackQueue>>,
+        notifier: PlaybackNotifier,
+        echo_reference: EchoReference,
+    ) -> Result<Self, String> {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (shutdown, shutdown_rx) = mpsc::channel::<()>();
+        let requested = device_name.clone();
+
+        let thread = thread::Builder::new()
+            .name("lily-audio-playback".to_string())
+            .spawn(move || match open(requested.as_deref(), queue, notifier, echo_reference) {
+                Ok((stream, format, mut drain)) => {
+                    let _ = ready_tx.send(Ok(format));
+                    // Runs until the worker is dropped
+                    while let Err(mpsc::RecvTimeoutError::Timeout) = shutdown_rx.recv_timeout(DRAIN_INTERVAL) {
+                        drain.drain();
+                    }
+                    drop(stream);
+                    drain.drain();
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                }
+            })
+            .map_err(|e| format!("Failed to spawn audio playback thread: {}", e))?;
+
+        let format = ready_rx
+            .recv()
+            .map_err(|_| "Audio playback thread exited during startup".to_string())??;
+
+        Ok(Self {
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+            format,
+            device_name,
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        self.thread.as_ref().is_some_and(|thread| !thread.is_finished())
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Drop for PlaybackWorker {
+    fn drop(&mut self) {
+        self.shutdown.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Plays TTS replies from Lily-Core on a native output device, one at a time.
+#[derive(Clone)]
+pub struct AudioPlaybackService {
+    queue: Arc<Mutex<PlaybackQueue>>,
+    raw_format: Arc<Mutex<PcmFormat>>,
+    output_device: Arc<Mutex<Option<String>>>,
+    notifier: PlaybackNotifier,
+    echo_reference: EchoReference,
+    #[cfg(feature = "audio")]
+    output: Arc<Mutex<Option<PlaybackWorker>>>,
+    #[cfg(feature = "audio")]
+    open_output: OpenOutput,
+}
+
+impl Default for AudioPlaybackService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioPlaybackService {
+    pub fn new() -> Self {
+        let (events_tx, _) = broadcast::channel(32);
+        Self {
+            queue: Arc::new(Mutex::new(PlaybackQueue::default())),
+            raw_format: Arc::new(Mutex::new(PcmFormat::default())),
+            output_device: Arc::new(Mutex::new(None)),
+            notifier: PlaybackNotifier {
+                events_tx,
+                sink: Arc::new(Mutex::new(Arc::new(NullEventSink))),
+            },
+            echo_reference: EchoReference::default(),
+            #[cfg(feature = "audio")]
+            output: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "audio")]
+            open_output: Arc::new(|device_name, queue, notifier, echo_reference| {
+                open_output_stream(device_name, queue, notifier, echo_reference)
+                    .map(|(stream, format, drain)| (Box::new(stream) as Box<dyn std::any::Any>, format, drain))
+            }),
+        }
+    }
+
+    /// Receives `tts-playback-started` and `tts-playback-finished`.
+    pub fn set_event_sink(&self, sink: Arc<dyn EventSink>) {
+        *self.notifier.sink.lock().unwrap() = sink;
+    }
+
+    /// Format used for replies that arrive without a WAV header.
+    pub fn set_raw_format(&self, format: PcmFormat) {
+        *self.raw_format.lock().unwrap() = format;
+    }
+
+    /// Output device by cpal name. The next reply reopens the output on it;
+    /// anything still queued carries on there.
+    pub fn set_output_device(&self, device_name: Option<String>) {
+        *self.output_device.lock().unwrap() = device_name;
+    }
+
+    /// What the speaker is playing, for echo suppression on the capture side.
+    pub fn echo_reference(&self) -> EchoReference {
+        self.echo_reference.clone()
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<PlaybackEvent> {
+        self.notifier.events_tx.subscribe()
+    }
+
+    /// Decodes a TTS reply and queues it behind anything already playing.
+    pub fn enqueue(&self, data: &[u8]) -> Result<u64, String> {
+        let raw_format = *self.raw_format.lock().unwrap();
+        let audio = decode_tts_audio(data, raw_format)?;
+        let format = self.ensure_output()?;
+        let samples = convert_for_output(&audio, format.channels, format.sample_rate);
+        Ok(self.queue.lock().unwrap().push(samples, audio.duration_ms()))
+    }
+
+    // Opens the output device on first use and keeps it open between replies,
+    // until it dies or a different device is selected
+    fn ensure_output(&self) -> Result<OutputFormat, String> {
+        #[cfg(feature = "audio")]
+        {
+            let mut output = self.output.lock().unwrap();
+            let device_name = self.output_device.lock().unwrap().clone();
+            let current = output
+                .as_ref()
+                .is_some_and(|worker| worker.is_alive() && worker.device_name == device_name);
+            if !current {
+                // Release the old device before opening the new one
+                *output = None;
+                *output = Some(PlaybackWorker::spawn(
+                    self.open_output.clone(),
+                    device_name,
+                    self.queue.clone(),
+                    self.notifier.clone(),
+                    self.echo_reference.clone(),
+                )?);
+            }
+            Ok(output.as_ref().unwrap().format)
+        }
+
+        #[cfg(not(feature = "audio"))]
+        {
+            Err("Audio not enabled".to_string())
+        }
+    }
+
+    pub fn pause(&self) {
+        self.queue.lock().unwrap().pause();
+    }
+
+    pub fn resume(&self) {
+        self.queue.lock().unwrap().resume();
+    }
+
+    pub fn skip(&self) {
+        let event = self.queue.lock().unwrap().skip();
+        if let Some(event) = event {
+            self.notifier.notify(event);
+        }
+    }
+
+    pub fn stop(&self) {
+        let event = self.queue.lock().unwrap().stop();
+        if let Some(event) = event {
+            self.notifier.notify(event);
+        }
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.queue.lock().unwrap().set_volume(volume);
+    }
+
+    pub fn set_duck(&self, gain: Option<f32>) {
+        self.queue.lock().unwrap().set_duck(gain);
+    }
+
+    pub fn status(&self) -> PlaybackStatus {
+        self.queue.lock().unwrap().status()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        let queue = self.queue.lock().unwrap();
+        queue.is_active() && !queue.status().paused
+    }
+}
+
+#[cfg(feature = "audio")]
+fn open_output_stream(
+    device_name: Option<&str>,
+    queue: Arc<Mutex<PlaybackQueue>>,
+    notifier: PlaybackNotifier,
+    echo_reference: EchoReference,
+) -> Result<(cpal::Stream, OutputFormat, OutputDrain), String> {
+    let host = cpal::default_host();
+    let device = select_output_device(&host, device_name)?;
+    let config = device.default_output_config()
+        .map_err(|e| format!("Failed to get default output config: {}", e))?;
+
+    log::info!("Playback device: {}", device.name().unwrap_or("Unknown".to_string()));
+    let format = OutputFormat {
+        channels: config.channels(),
+        sample_rate: config.sample_rate().0,
+    };
+    let (renderer, drain) = output_pipeline(format, queue, notifier, echo_reference);
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => create_output_stream::<f32>(&device, config.into(), renderer)?,
+        cpal::SampleFormat::I16 => create_output_stream::<i16>(&device, config.into(), renderer)?,
+        cpal::SampleFormat::U16 => create_output_stream::<u16>(&device, config.into(), renderer)?,
+        _ => return Err(format!("Unsupported sample format: {:?}", config.sample_format())),
+    };
+
+    stream.play().map_err(|e| format!("Failed to start playback stream: {}", e))?;
+    Ok((stream, format, drain))
+}
+
+#[cfg(feature = "audio")]
+fn select_output_device(host: &cpal::Host, requested: Option<&str>) -> Result<cpal::Device, String> {
+    if let Some(name) = requested {
+        let mut devices = host.output_devices()
+            .map_err(|e| format!("Failed to get output devices: {}", e))?
+            .filter_map(|device| device.name().ok().map(|device_name| (device_name, device)))
+            .collect::<Vec<_>>();
+        let names = devices.iter().map(|(device_name, _)| device_name.clone()).collect::<Vec<_>>();
+
+        if let Some(index) = match_device_name(&names, name) {
+            return Ok(devices.swap_remove(index).1);
+        }
+        log::warn!("Output device '{}' is not available, falling back to the default output device", name);
+    }
+
+    host.default_output_device().ok_or_else(|| "No default output device found".to_string())
+}
+
+#[cfg(feature = "audio")]
+fn create_output_stream<T>(
+    device: &cpal::Device,
+    config: cpal::StreamConfig,
+    mut renderer: OutputRenderer,
+) -> Result<cpal::Stream, String>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    device.build_output_stream(
+        &config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let len = data.len();
+            for (out, &sample) in data.iter_mut().zip(renderer.render(len)) {
+                *out = T::from_sample(sample);
+            }
+        },
+        move |err| {
+            log::error!("Audio playback stream error: {}", err);
+        },
+        None,
+    ).map_err(|e| format!("Failed to build output stream: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::wav::WAVE_FORMAT_IEEE_FLOAT;
+
+    fn wav(format_tag: u16, channels: u16, sample_rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&format_tag.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        let block_align = channels * bits / 8;
+        bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn pcm16(values: &[i16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_decode_pcm16_wav() {
+        let data = wav(WAVE_FORMAT_PCM, 2, 22_050, 16, &pcm16(&[0, 16384, -32768, 0]));
+        let audio = decode_tts_audio(&data, PcmFormat::default()).unwrap();
+
+        assert_eq!(audio.sample_rate, 22_050);
+        assert_eq!(audio.channels, 2);
+        assert_eq!(audio.samples, vec![0.0, 0.5, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_decode_float_wav_with_placeholder_size() {
+        let samples: Vec<u8> = [0.25f32, -0.25].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let mut data = wav(WAVE_FORMAT_IEEE_FLOAT, 1, 24_000, 32, &samples);
+        // Streaming encoders write 0xFFFFFFFF before the length is known
+        let data_size_offset = data.len() - samples.len() - 4;
+        data[data_size_offset..data_size_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let audio = decode_tts_audio(&data, PcmFormat::default()).unwrap();
+        assert_eq!(audio.samples, vec![0.25, -0.25]);
+    }
+
+    #[test]
+    fn test_headerless_audio_uses_raw_format() {
+        let raw = PcmFormat { sample_rate: 16_000, channels: 1 };
+        let audio = decode_tts_audio(&pcm16(&[16384; 1600]), raw).unwrap();
+
+        assert_eq!(audio.sample_rate, 16_000);
+        assert_eq!(audio.duration_ms(), 100);
+    }
+
+    #[test]
+    fn test_unsupported_wav_is_an_error() {
+        let data = wav(0x0055, 1, 24_000, 16, &[0; 4]); // MP3-in-WAV
+        assert!(decode_tts_audio(&data, PcmFormat::default()).is_err());
+    }
+
+    #[test]
+    fn test_convert_for_output_upmixes_and_resamples() {
+        let audio = DecodedAudio {
+            samples: vec![0.5; 240], // 10 ms at 24 kHz
+            sample_rate: 24_000,
+            channels: 1,
+        };
+        let output = convert_for_output(&audio, 2, 48_000);
+        assert_eq!(output.len(), 480 * 2);
+        assert!(output.iter().all(|&s| (s - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_queue_plays_clips_in_order() {
+        let mut queue = PlaybackQueue::default();
+        let first = queue.push(vec![0.1; 3], 0);
+        let second = queue.push(vec![0.2; 2], 0);
+
+        let mut events = Vec::new();
+        let mut output = vec![1.0; 8];
+        queue.fill(&mut output, &mut events);
+
+        assert_eq!(output, vec![0.1, 0.1, 0.1, 0.2, 0.2, 0.0, 0.0, 0.0]);
+        assert_eq!(events, vec![
+            PlaybackEvent::Started { id: first, duration_ms: 0 },
+            PlaybackEvent::Finished { id: first, reason: FinishReason::Completed },
+            PlaybackEvent::Started { id: second, duration_ms: 0 },
+            PlaybackEvent::Finished { id: second, reason: FinishReason::Completed },
+        ]);
+        assert!(!queue.is_active());
+    }
+
+    #[test]
+    fn test_queue_continues_clip_across_callbacks() {
+        let mut queue = PlaybackQueue::default();
+        queue.push(vec![0.1, 0.2, 0.3, 0.4], 0);
+
+        let mut events = Vec::new();
+        let mut output = vec![0.0; 3];
+        queue.fill(&mut output, &mut events);
+        assert_eq!(output, vec![0.1, 0.2, 0.3]);
+        assert_eq!(events.len(), 1);
+
+        queue.fill(&mut output, &mut events);
+        assert_eq!(output, vec![0.4, 0.0, 0.0]);
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn test_pause_volume_skip_and_stop() {
+        let mut queue = PlaybackQueue::default();
+        let first = queue.push(vec![1.0; 8], 0);
+        let second = queue.push(vec![1.0; 8], 0);
+        queue.push(vec![1.0; 8], 0);
+
+        let mut events = Vec::new();
+        let mut output = vec![0.0; 2];
+
+        queue.set_volume(0.5);
+        queue.fill(&mut output, &mut events);
+        assert_eq!(output, vec![0.5, 0.5]);
+
+        queue.set_duck(Some(0.5));
+        queue.fill(&mut output, &mut events);
+        assert_eq!(output, vec![0.25, 0.25]);
+        assert!(queue.status().ducked);
+        queue.set_duck(None);
+        assert_eq!(queue.status().volume, 0.5);
+
+        queue.pause();
+        queue.fill(&mut output, &mut events);
+        assert_eq!(output, vec![0.0, 0.0]);
+        assert_eq!(queue.status().playing, Some(first));
+
+        queue.resume();
+        assert_eq!(queue.skip(), Some(PlaybackEvent::Finished { id: first, reason: FinishReason::Skipped }));
+        queue.fill(&mut output, &mut events);
+        assert_eq!(queue.status().playing, Some(second));
+        assert_eq!(queue.status().queued, 1);
+
+        assert_eq!(queue.stop(), Some(PlaybackEvent::Finished { id: second, reason: FinishReason::Stopped }));
+        assert!(!queue.is_active());
+    }
+
+    #[test]
+    fn test_service_controls_publish_events() {
+        let service = AudioPlaybackService::new();
+        let mut events = service.subscribe_events();
+        let id = service.queue.lock().unwrap().push(vec![0.0; 4], 0);
+        service.queue.lock().unwrap().fill(&mut [0.0; 1], &mut Vec::new());
+
+        assert!(service.is_playing());
+        service.stop();
+        assert_eq!(events.try_recv().unwrap(), PlaybackEvent::Finished { id, reason: FinishReason::Stopped });
+        assert!(!service.is_playing());
+    }
+
+    #[test]
+    fn test_renderer_hands_reference_and_events_to_the_drain() {
+        let service = AudioPlaybackService::new();
+        let mut events = service.subscribe_events();
+        let reference = EchoReference::new(8_000, 1000);
+        let format = OutputFormat { channels: 2, sample_rate: 8_000 };
+        let (mut renderer, mut drain) = output_pipeline(format, service.queue.clone(), service.notifier.clone(), reference.clone());
+        let id = service.queue.lock().unwrap().push(vec![0.5; 160], 10);
+
+        let played = renderer.render(200).to_vec();
+        assert_eq!(played[..160], [0.5; 160]);
+        assert_eq!(played[160..], [0.0; 40]);
+        // Nothing leaves the audio thread until the playback thread drains it
+        assert!(reference.latest(1000).is_empty());
+        assert!(events.try_recv().is_err());
+
+        drain.drain();
+        let mono = reference.latest(1000);
+        assert!(mono.len() >= 99);
+        assert!(mono.iter().filter(|&&sample| sample == 0.5).count() >= 79);
+        assert_eq!(events.try_recv().unwrap(), PlaybackEvent::Started { id, duration_ms: 10 });
+        assert_eq!(events.try_recv().unwrap(), PlaybackEvent::Finished { id, reason: FinishReason::Completed });
+    }
+
+    #[test]
+    fn test_renderer_plays_silence_while_queue_is_locked() {
+        let service = AudioPlaybackService::new();
+        let format = OutputFormat { channels: 1, sample_rate: 8_000 };
+        let (mut renderer, _drain) = output_pipeline(format, service.queue.clone(), service.notifier.clone(), EchoReference::default());
+        service.queue.lock().unwrap().push(vec![0.5; 16], 0);
+
+        let queue = service.queue.lock().unwrap();
+        assert_eq!(renderer.render(8), [0.0; 8]);
+        drop(queue);
+        assert_eq!(renderer.render(8), [0.5; 8]);
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_changing_output_device_reopens_on_next_reply() {
+        let opened = Arc::new(Mutex::new(Vec::new()));
+        let mut service = AudioPlaybackService::new();
+        let log = opened.clone();
+        service.open_output = Arc::new(move |device_name, queue, notifier, echo_reference| {
+            log.lock().unwrap().push(device_name.map(str::to_string));
+            let format = OutputFormat { channels: 1, sample_rate: 24_000 };
+            let (_renderer, drain) = output_pipeline(format, queue, notifier, echo_reference);
+            Ok((Box::new(()) as Box<dyn std::any::Any>, format, drain))
+        });
+        let reply = pcm16(&[0; 240]);
+
+        service.enqueue(&reply).unwrap();
+        service.enqueue(&reply).unwrap();
+        assert_eq!(*opened.lock().unwrap(), vec![None]);
+
+        service.set_output_device(Some("USB Headset".to_string()));
+        service.enqueue(&reply).unwrap();
+        assert_eq!(*opened.lock().unwrap(), vec![None, Some("USB Headset".to_string())]);
+        assert_eq!(service.status().queued, 3);
+    }
+}
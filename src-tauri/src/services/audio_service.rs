@@ -1,108 +1,331 @@
-use std::sync::{Arc, Mutex};
-use tokio::sync::broadcast;
+use serde::{Deserialize, Serialize};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use crate::domain::error::LilyError;
+use crate::services::audio_frames::{frame_channel, FrameOutput, FrameReader, FrameStats, FrameStatsSnapshot, FrameWriter};
+use crate::services::audio_meter::{rms, AudioMeter, AudioMeterReading};
+use crate::services::audio_pipeline::{encode_samples, AudioPipeline, StreamEncoding, StreamSettings};
+use crate::services::audio_source::{AudioSource, CaptureStream, SampleCallback, SourceFormat};
 #[cfg(feature = "audio")]
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-#[cfg(feature = "audio")]
-use ringbuf::HeapRb;
-#[cfg(feature = "tauri")]
-use tauri::Emitter;
-#[cfg(feature = "tauri")]
-use crate::domain::interfaces::WebSocketTrait;
-#[cfg(feature = "tauri")]
-use crate::infrastructure::websocket::WebSocketService;
+use crate::services::audio_source::CpalSource;
+use crate::services::echo::{EchoReference, EchoSettings, EchoSuppressor};
+use crate::services::vad::{VadSettings, VadTransition, VoiceGate};
+use tokio::runtime::Handle;
+use tokio::sync::{broadcast, oneshot};
+use crate::services::events::{EventSink, NullEventSink};
+
+// Seconds of audio the capture ring buffer holds while the sender is blocked
+const FRAME_BUFFER_SECONDS: usize = 2;
+
+type CommandReply<T = ()> = oneshot::Sender<Result<T, String>>;
+
+// Commands understood by the capture thread
+enum CaptureCommand {
+    Start { context: Box<CaptureContext>, reply: CommandReply<InputDeviceSelection> },
+    Pause { reply: CommandReply },
+    Resume { reply: CommandReply },
+    Stop { reply: CommandReply },
+}
+
+// Everything the sample callback and frame sender need, handed to the capture thread on start
+struct CaptureContext {
+    device_name: Option<String>,
+    stream_settings: StreamSettings,
+    vad_settings: VadSettings,
+    echo_settings: EchoSettings,
+    echo_reference: Option<EchoReference>,
+    audio_level_tx: broadcast::Sender<f32>,
+    meter: Arc<Mutex<AudioMeter>>,
+    recording: Arc<Mutex<bool>>,
+    paused: Arc<Mutex<bool>>,
+    speech_tx: broadcast::Sender<VadTransition>,
+    frame_stats: Arc<FrameStats>,
+    frame_output: Option<Arc<dyn FrameOutput>>,
+    // Runtime the frame sender runs on; captured from the caller of `start_recording`
+    runtime: Option<Handle>,
+    events: Arc<dyn EventSink>,
+}
+
+// Handle to the thread that owns the source's stream (cpal streams are not
+// `Send`). Dropping it closes the command channel, which makes the thread drop
+// the stream and exit.
+struct CaptureWorker {
+    commands: Option<mpsc::Sender<CaptureCommand>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl CaptureWorker {
+    fn spawn(source: Arc<dyn AudioSource>) -> Result<Self, String> {
+        let (commands, receiver) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("lily-audio-capture".to_string())
+            .spawn(move || run_capture_worker(source, receiver))
+            .map_err(|e| format!("Failed to spawn audio capture thread: {}", e))?;
+
+        Ok(Self {
+            commands: Some(commands),
+            thread: Some(thread),
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        self.thread.as_ref().is_some_and(|thread| !thread.is_finished())
+    }
+
+    fn send(&self, command: CaptureCommand) -> Result<(), String> {
+        self.commands
+            .as_ref()
+            .ok_or("Audio capture thread is shutting down")?
+            .send(command)
+            .map_err(|_| "Audio capture thread has stopped".to_string())
+    }
+}
+
+impl Drop for CaptureWorker {
+    fn drop(&mut self) {
+        self.commands.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The input device a recording actually opened, and whether that differs
+/// from the one that was asked for.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InputDeviceSelection {
+    pub device: String,
+    pub requested: Option<String>,
+    pub fell_back: bool,
+}
 
 #[derive(Clone)]
 pub struct AudioService {
     is_recording: Arc<Mutex<bool>>,
+    is_paused: Arc<Mutex<bool>>,
+    stream_settings: Arc<Mutex<StreamSettings>>,
+    vad_settings: Arc<Mutex<VadSettings>>,
+    echo_settings: Arc<Mutex<EchoSettings>>,
+    echo_reference: Arc<Mutex<Option<EchoReference>>>,
     audio_level_tx: broadcast::Sender<f32>,
-    #[cfg(feature = "tauri")]
-    app_handle: Arc<Mutex<Option<tauri::AppHandle>>>,
+    meter: Arc<Mutex<AudioMeter>>,
+    speech_tx: broadcast::Sender<VadTransition>,
+    frame_stats: Arc<Mutex<Arc<FrameStats>>>,
+    frame_output: Arc<Mutex<Option<Arc<dyn FrameOutput>>>>,
+    source: Option<Arc<dyn AudioSource>>,
+    capture: Arc<Mutex<Option<CaptureWorker>>>,
+    events: Arc<Mutex<Arc<dyn EventSink>>>,
+}
+
+impl Default for AudioService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AudioService {
+    /// Captures from the system's input devices (none without the `audio` feature).
     pub fn new() -> Self {
+        #[cfg(feature = "audio")]
+        let source: Option<Arc<dyn AudioSource>> = Some(Arc::new(CpalSource));
+        #[cfg(not(feature = "audio"))]
+        let source = None;
+        Self::from_source(source)
+    }
+
+    /// Captures from `source` instead, e.g. a WAV file or a synthetic signal.
+    pub fn with_source(source: Arc<dyn AudioSource>) -> Self {
+        Self::from_source(Some(source))
+    }
+
+    fn from_source(source: Option<Arc<dyn AudioSource>>) -> Self {
         let (audio_level_tx, _) = broadcast::channel(100);
+        let (speech_tx, _) = broadcast::channel(16);
         Self {
             is_recording: Arc::new(Mutex::new(false)),
+            is_paused: Arc::new(Mutex::new(false)),
+            stream_settings: Arc::new(Mutex::new(StreamSettings::default())),
+            vad_settings: Arc::new(Mutex::new(VadSettings::default())),
+            echo_settings: Arc::new(Mutex::new(EchoSettings::default())),
+            echo_reference: Arc::new(Mutex::new(None)),
             audio_level_tx,
-            #[cfg(feature = "tauri")]
-            app_handle: Arc::new(Mutex::new(None)),
+            meter: Arc::new(Mutex::new(AudioMeter::default())),
+            speech_tx,
+            frame_stats: Arc::new(Mutex::new(Arc::new(FrameStats::default()))),
+            frame_output: Arc::new(Mutex::new(None)),
+            source,
+            capture: Arc::new(Mutex::new(None)),
+            events: Arc::new(Mutex::new(Arc::new(NullEventSink))),
         }
     }
 
-    #[cfg(feature = "tauri")]
-    pub fn set_app_handle(&self, app_handle: tauri::AppHandle) {
-        *self.app_handle.lock().unwrap() = Some(app_handle);
+    /// Receives `audio-level` and `speech-start`/`speech-end`; applies from the next `start_recording`.
+    pub fn set_event_sink(&self, events: Arc<dyn EventSink>) {
+        *self.events.lock().unwrap() = events;
+    }
+
+    /// Where encoded frames are streamed; applies from the next `start_recording`.
+    /// Without one, frames still go through voice detection but are not sent.
+    pub fn set_frame_output(&self, output: Arc<dyn FrameOutput>) {
+        *self.frame_output.lock().unwrap() = Some(output);
+    }
+
+    /// Target format for streamed audio; applies from the next `start_recording`.
+    pub fn set_stream_settings(&self, settings: StreamSettings) {
+        *self.stream_settings.lock().unwrap() = settings;
+    }
+
+    pub fn stream_settings(&self) -> StreamSettings {
+        *self.stream_settings.lock().unwrap()
     }
 
     pub fn subscribe_audio_levels(&self) -> broadcast::Receiver<f32> {
         self.audio_level_tx.subscribe()
     }
 
-    pub async fn start_recording(&self) -> Result<(), String> {
-        #[cfg(feature = "audio")]
-        {
-            let host = cpal::default_host();
-            let device = host.default_input_device()
-                .ok_or("No default input device found")?;
+    /// Voice detection settings; applies from the next `start_recording`.
+    pub fn set_vad_settings(&self, settings: VadSettings) {
+        *self.vad_settings.lock().unwrap() = settings;
+    }
 
-            let config = device.default_input_config()
-                .map_err(|e| format!("Failed to get default input config: {}", e))?;
+    pub fn vad_settings(&self) -> VadSettings {
+        *self.vad_settings.lock().unwrap()
+    }
 
-            println!("Audio device: {}", device.name().unwrap_or("Unknown".to_string()));
-            println!("Audio config: {:?}, sample rate: {}", config.sample_format(), config.sample_rate().0);
+    /// Latest RMS of the captured signal; 0.0 when not capturing.
+    pub fn audio_level(&self) -> f32 {
+        self.meter.lock().unwrap().reading().rms
+    }
 
+    pub fn audio_meter(&self) -> AudioMeterReading {
+        self.meter.lock().unwrap().reading()
+    }
+
+    /// Echo suppression settings; applies from the next `start_recording`.
+    pub fn set_echo_settings(&self, settings: EchoSettings) {
+        *self.echo_settings.lock().unwrap() = settings;
+    }
+
+    pub fn echo_settings(&self) -> EchoSettings {
+        *self.echo_settings.lock().unwrap()
+    }
+
+    /// Speaker output to suppress from the microphone, usually from `AudioPlaybackService`.
+    pub fn set_echo_reference(&self, reference: EchoReference) {
+        *self.echo_reference.lock().unwrap() = Some(reference);
+    }
+
+    pub fn subscribe_speech(&self) -> broadcast::Receiver<VadTransition> {
+        self.speech_tx.subscribe()
+    }
+
+    /// Frame counters for the current (or last) recording.
+    pub fn frame_stats(&self) -> FrameStatsSnapshot {
+        self.frame_stats.lock().unwrap().snapshot()
+    }
+
+    pub async fn start_recording(&self) -> Result<InputDeviceSelection, LilyError> {
+        self.start_recording_with_device(None).await
+    }
+
+    /// Starts capturing from the named input device, falling back to the
+    /// system default (and reporting it) when that device is not present.
+    pub async fn start_recording_with_device(&self, device_name: Option<String>) -> Result<InputDeviceSelection, LilyError> {
+        if self.source.is_none() {
+            return Err(LilyError::audio("Audio not enabled"));
+        }
+
+        {
             let mut is_recording = self.is_recording.lock().unwrap();
             if *is_recording {
-                return Err("Already recording".to_string());
+                return Err(LilyError::validation("Already recording"));
             }
             *is_recording = true;
-            drop(is_recording);
-
-            // Create audio buffer for RMS calculation
-            let ring_buf = HeapRb::<f32>::new(8192);
-            let (producer, _consumer) = ring_buf.split();
-
-            let audio_level_tx = self.audio_level_tx.clone();
-            let is_recording_clone = self.is_recording.clone();
-            #[cfg(feature = "tauri")]
-            let app_handle = self.app_handle.lock().unwrap().clone();
-            #[cfg(not(feature = "tauri"))]
-            let app_handle = None;
-
-            let stream = match config.sample_format() {
-                cpal::SampleFormat::F32 => self.create_stream::<f32>(&device, config.into(), producer, audio_level_tx, is_recording_clone, app_handle)?,
-                cpal::SampleFormat::I16 => self.create_stream::<i16>(&device, config.into(), producer, audio_level_tx, is_recording_clone, app_handle)?,
-                cpal::SampleFormat::U16 => self.create_stream::<u16>(&device, config.into(), producer, audio_level_tx, is_recording_clone, app_handle)?,
-                _ => return Err(format!("Unsupported sample format: {:?}", config.sample_format())),
-            };
-
-            stream.play().map_err(|e| format!("Failed to start stream: {}", e))?;
-
-            // Store the stream in a way that doesn't require Send/Sync
-            // We'll let it drop when the function ends, but the audio callback will keep it alive
-            std::mem::forget(stream);
+        }
 
-            println!("Audio recording started successfully");
-            Ok(())
+        // Fresh counters per recording
+        let frame_stats = Arc::new(FrameStats::default());
+        *self.frame_stats.lock().unwrap() = frame_stats.clone();
+
+        let context = CaptureContext {
+            device_name,
+            stream_settings: self.stream_settings(),
+            vad_settings: self.vad_settings(),
+            echo_settings: self.echo_settings(),
+            echo_reference: self.echo_reference.lock().unwrap().clone(),
+            audio_level_tx: self.audio_level_tx.clone(),
+            meter: self.meter.clone(),
+            recording: self.is_recording.clone(),
+            paused: self.is_paused.clone(),
+            speech_tx: self.speech_tx.clone(),
+            frame_stats,
+            frame_output: self.frame_output.lock().unwrap().clone(),
+            runtime: Handle::try_current().ok(),
+            events: self.events.lock().unwrap().clone(),
+        };
+
+        let result = self.send_capture_command(|reply| CaptureCommand::Start { context: Box::new(context), reply }).await;
+        match &result {
+            Ok(selection) => {
+                *self.is_paused.lock().unwrap() = false;
+                log::info!("Audio recording started successfully on '{}'", selection.device);
+            }
+            Err(_) => *self.is_recording.lock().unwrap() = false,
         }
+        result
+    }
 
-        #[cfg(not(feature = "audio"))]
+    pub async fn stop_recording(&self) -> Result<(), LilyError> {
         {
-            Err("Audio not enabled".to_string())
+            let mut is_recording = self.is_recording.lock().unwrap();
+            if !*is_recording {
+                return Ok(());
+            }
+            *is_recording = false;
+        }
+        *self.is_paused.lock().unwrap() = false;
+
+        // Dropping the stream on the capture thread releases the input device
+        self.send_capture_command(|reply| CaptureCommand::Stop { reply }).await?;
+        self.meter.lock().unwrap().reset();
+
+        let stats = self.frame_stats();
+        log::info!(
+            "Audio recording stopped ({} frames sent, {} send failures, {} samples dropped)",
+            stats.frames_sent, stats.send_failures, stats.samples_dropped
+        );
+        Ok(())
+    }
+
+    pub async fn pause_recording(&self) -> Result<(), LilyError> {
+        if !self.is_recording() {
+            return Err(LilyError::validation("Not recording"));
+        }
+        if self.is_paused() {
+            return Ok(());
         }
+
+        self.send_capture_command(|reply| CaptureCommand::Pause { reply }).await?;
+
+        *self.is_paused.lock().unwrap() = true;
+        // No callbacks while paused, so don't keep reporting the last level
+        self.meter.lock().unwrap().reset();
+        log::info!("Audio recording paused");
+        Ok(())
     }
 
-    pub async fn stop_recording(&self) -> Result<(), String> {
-        let mut is_recording = self.is_recording.lock().unwrap();
-        if !*is_recording {
+    pub async fn resume_recording(&self) -> Result<(), LilyError> {
+        if !self.is_recording() {
+            return Err(LilyError::validation("Not recording"));
+        }
+        if !self.is_paused() {
             return Ok(());
         }
-        *is_recording = false;
-        drop(is_recording);
 
-        // The stream will be stopped when the callback checks is_recording and returns early
-        println!("Audio recording stopped");
+        self.send_capture_command(|reply| CaptureCommand::Resume { reply }).await?;
+
+        *self.is_paused.lock().unwrap() = false;
+        log::info!("Audio recording resumed");
         Ok(())
     }
 
@@ -110,102 +333,240 @@ impl AudioService {
         *self.is_recording.lock().unwrap()
     }
 
-    #[cfg(feature = "audio")]
-    fn create_stream<T>(
-        &self,
-        device: &cpal::Device,
-        config: cpal::StreamConfig,
-        mut producer: ringbuf::Producer<f32, Arc<ringbuf::SharedRb<f32, Vec<std::mem::MaybeUninit<f32>>>>>,
-        audio_level_tx: broadcast::Sender<f32>,
-        is_recording: Arc<Mutex<bool>>,
-        app_handle: Option<tauri::AppHandle>,
-    ) -> Result<cpal::Stream, String>
-    where
-        T: cpal::Sample + Into<f32> + cpal::SizedSample,
-    {
-        let stream = device.build_input_stream(
-            &config,
-            move |data: &[T], _: &cpal::InputCallbackInfo| {
-                // Check if we should still be recording
-                if !*is_recording.lock().unwrap() {
-                    return;
-                }
+    pub fn is_paused(&self) -> bool {
+        *self.is_paused.lock().unwrap()
+    }
 
-                // Convert samples to f32 and push to ring buffer
-                for &sample in data {
-                    let sample_f32 = sample.into();
-                    // Try to push, ignore if buffer is full (ring buffer will overwrite oldest)
-                    let _ = producer.push(sample_f32);
-                }
+    // Sends a command to the capture thread (spawning it on first use) and waits for its reply
+    async fn send_capture_command<T>(&self, command: impl FnOnce(CommandReply<T>) -> CaptureCommand) -> Result<T, LilyError> {
+        let source = self.source.clone().ok_or_else(|| LilyError::audio("Audio not enabled"))?;
+        let (reply, response) = oneshot::channel();
+        {
+            let mut capture = self.capture.lock().unwrap();
+            if !capture.as_ref().is_some_and(CaptureWorker::is_alive) {
+                *capture = Some(CaptureWorker::spawn(source).map_err(LilyError::audio)?);
+            }
+            capture.as_ref().unwrap().send(command(reply)).map_err(LilyError::audio)?;
+        }
 
-                // Send audio data via WebSocket
-                if let Some(handle) = &app_handle {
-                    let mut bytes = Vec::new();
-                    for &sample in data {
-                        let sample_f32 = sample.into();
-                        bytes.extend_from_slice(&sample_f32.to_le_bytes());
-                    }
-                    let handle = handle.clone();
-                    tauri::async_runtime::spawn(async move {
-                        let _ = WebSocketService::send_binary_data(bytes, handle).await;
-                    });
-                }
+        response.await
+            .map_err(|_| LilyError::audio("Audio capture thread dropped the request"))?
+            .map_err(LilyError::audio)
+    }
 
-                // Calculate RMS on every callback that has samples
-                if data.len() > 0 {
-                    let mut sum_squares = 0.0;
-                    let mut count = 0;
-
-                    // Calculate RMS from all samples in this callback
-                    for &sample in data {
-                        let sample_f32 = sample.into();
-                        sum_squares += sample_f32 * sample_f32;
-                        count += 1;
-                    }
-
-                    if count > 0 {
-                        let rms = (sum_squares / count as f32).sqrt();
-                        let _ = audio_level_tx.send(rms);
-
-                        // Emit event to frontend
-                        if let Some(app_handle) = &app_handle {
-                            let _ = app_handle.emit("audio-level", rms);
-                        }
-                    }
-                }
-            },
-            move |err| {
-                eprintln!("Audio stream error: {}", err);
-            },
-            None,
-        ).map_err(|e| format!("Failed to build input stream: {}", e))?;
+    pub fn get_available_devices(&self) -> Result<Vec<String>, LilyError> {
+        match &self.source {
+            Some(source) => source.list_devices().map_err(LilyError::audio),
+            // Audio not enabled, return empty list
+            None => Ok(vec![]),
+        }
+    }
+}
 
-        Ok(stream)
+// Body of the capture thread. The stream lives only here, so stopping (or the
+// service going away) really drops it and releases the device.
+fn run_capture_worker(source: Arc<dyn AudioSource>, commands: mpsc::Receiver<CaptureCommand>) {
+    let mut stream: Option<Box<dyn CaptureStream>> = None;
+
+    while let Ok(command) = commands.recv() {
+        match command {
+            CaptureCommand::Start { context, reply } => {
+                let result = if stream.is_some() {
+                    Err("Already recording".to_string())
+                } else {
+                    open_input_stream(source.as_ref(), *context).map(|(opened, selection)| {
+                        stream = Some(opened);
+                        selection
+                    })
+                };
+                let _ = reply.send(result);
+            }
+            CaptureCommand::Pause { reply } => {
+                let result = match &stream {
+                    Some(stream) => stream.pause(),
+                    None => Err("Not recording".to_string()),
+                };
+                let _ = reply.send(result);
+            }
+            CaptureCommand::Resume { reply } => {
+                let result = match &stream {
+                    Some(stream) => stream.resume(),
+                    None => Err("Not recording".to_string()),
+                };
+                let _ = reply.send(result);
+            }
+            CaptureCommand::Stop { reply } => {
+                stream = None;
+                let _ = reply.send(Ok(()));
+            }
+        }
     }
+}
 
-    pub fn get_available_devices(&self) -> Result<Vec<String>, String> {
-        #[cfg(feature = "audio")]
-        {
-            let host = cpal::default_host();
-            let devices = host.input_devices()
-                .map_err(|e| format!("Failed to get input devices: {}", e))?
-                .filter_map(|device| device.name().ok())
-                .collect::<Vec<_>>();
+fn open_input_stream(source: &dyn AudioSource, context: CaptureContext) -> Result<(Box<dyn CaptureStream>, InputDeviceSelection), String> {
+    // Processed samples are batched into fixed-duration frames through a ring
+    // buffer; a single sender task drains it so frames go out in order
+    let settings = context.stream_settings;
+    let frame_len = settings.frame_len();
+    let capacity = (settings.sample_rate as usize * FRAME_BUFFER_SECONDS).max(frame_len * 4);
+    let (writer, reader) = frame_channel(capacity, frame_len, context.frame_stats.clone());
+
+    // Keep enough speaker history to cover the acoustic delay plus one frame
+    let echo_history_ms = context.echo_settings.max_delay_ms + 2 * settings.frame_ms();
+    if let Some(reference) = &context.echo_reference {
+        reference.configure(settings.sample_rate, echo_history_ms);
+    }
 
-            Ok(devices)
+    match &context.runtime {
+        Some(runtime) => {
+            let sender = FrameSender {
+                encoding: settings.encoding,
+                echo: context.echo_reference.clone()
+                    .map(|reference| EchoSuppressor::new(context.echo_settings, reference)),
+                gate: VoiceGate::new(context.vad_settings, settings.sample_rate),
+                stats: context.frame_stats.clone(),
+                speech_tx: context.speech_tx.clone(),
+                output: context.frame_output.clone(),
+                audio_level_tx: context.audio_level_tx.clone(),
+                meter: context.meter.clone(),
+                recording: context.recording.clone(),
+                paused: context.paused.clone(),
+                events: context.events.clone(),
+            };
+            runtime.spawn(sender.run(reader));
         }
+        // Started outside a Tokio runtime: nothing can be streamed or metered
+        None => drop(reader),
+    }
 
-        #[cfg(not(feature = "audio"))]
+    let device_name = context.device_name.clone();
+    let opened = source.open(
+        device_name.as_deref(),
+        Box::new(move |format| capture_callback(format, writer, context)),
+    )?;
+    Ok((opened.stream, opened.selection))
+}
+
+// Exact name first, then a case-insensitive match (some hosts vary capitalisation)
+pub(crate) fn match_device_name(available: &[String], requested: &str) -> Option<usize> {
+    let requested = requested.trim();
+    available.iter().position(|name| name == requested)
+        .or_else(|| available.iter().position(|name| name.trim().eq_ignore_ascii_case(requested)))
+}
+
+// Runs on the source's thread (the real-time audio callback for cpal). It only
+// converts into reused buffers and writes the ring buffer: no locks, no events.
+// Levels are measured by the frame sender.
+fn capture_callback(format: SourceFormat, mut frames: FrameWriter, context: CaptureContext) -> SampleCallback {
+    let mut pipeline = AudioPipeline::new(format.channels, format.sample_rate, context.stream_settings);
+    // Sized for 100 ms of input; only grows if the device delivers bigger chunks
+    let mut buffer = Vec::with_capacity(format.sample_rate as usize / 10);
+
+    Box::new(move |samples: &[f32]| {
+        pipeline.process_into(samples, &mut buffer);
+        frames.write(&buffer);
+    })
+}
+
+// Drains whole frames from the ring buffer and sends them one at a time, so
+// frames stay ordered and a slow socket backs up into the ring buffer (where
+// overflow is counted) instead of spawning unbounded tasks. Voice detection
+// runs here too, on the same fixed-size frames that go to the server.
+struct FrameSender {
+    encoding: StreamEncoding,
+    echo: Option<EchoSuppressor>,
+    gate: VoiceGate,
+    stats: Arc<FrameStats>,
+    speech_tx: broadcast::Sender<VadTransition>,
+    output: Option<Arc<dyn FrameOutput>>,
+    audio_level_tx: broadcast::Sender<f32>,
+    meter: Arc<Mutex<AudioMeter>>,
+    recording: Arc<Mutex<bool>>,
+    paused: Arc<Mutex<bool>>,
+    events: Arc<dyn EventSink>,
+}
+
+impl FrameSender {
+    async fn run(mut self, mut reader: FrameReader) {
+        loop {
+            let closed = reader.is_closed();
+            while let Some(frame) = reader.next_frame() {
+                self.measure(&frame);
+                self.handle_frame(frame).await;
+            }
+            if closed {
+                // Stream stopped: send whatever is left as a short final frame
+                if let Some(rest) = reader.flush() {
+                    self.handle_frame(rest).await;
+                }
+                if let Some(transition) = self.gate.finish() {
+                    self.announce(transition);
+                }
+                break;
+            }
+            reader.wait().await;
+        }
+    }
+
+    async fn handle_frame(&mut self, mut frame: Vec<f32>) {
+        // Remove our own TTS before voice detection, so it can't trigger barge-in
+        if let Some(echo) = self.echo.as_mut() {
+            if echo.process(&mut frame) {
+                self.stats.record_echo_suppressed();
+            }
+        }
+
+        let output = self.gate.push(frame);
+        self.stats.record_skipped(output.skipped);
+        if let Some(transition) = output.transition {
+            self.announce(transition);
+        }
+
+        let Some(sink) = self.output.clone() else {
+            return;
+        };
+        for frame in output.frames {
+            match sink.send_frame(encode_samples(&frame, self.encoding)).await {
+                Ok(()) => self.stats.record_sent(),
+                Err(_) => self.stats.record_send_failure(),
+            }
+        }
+    }
+
+    // Stop and pause clear their flag before resetting the meter, so checking
+    // it under the meter lock keeps a frame captured earlier from bringing the
+    // last level back
+    fn measure(&self, frame: &[f32]) {
+        let level = rms(frame);
         {
-            // Audio not enabled, return empty list
-            Ok(vec![])
+            let mut meter = self.meter.lock().unwrap();
+            if *self.recording.lock().unwrap() && !*self.paused.lock().unwrap() {
+                meter.update(frame);
+            }
         }
+        let _ = self.audio_level_tx.send(level);
+        let _ = self.events.emit("audio-level", level);
+    }
+
+    fn announce(&self, transition: VadTransition) {
+        let _ = self.speech_tx.send(transition);
+        let event = match transition {
+            VadTransition::SpeechStart => "speech-start",
+            VadTransition::SpeechEnd { .. } => "speech-end",
+        };
+        let _ = self.events.emit(event, transition);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::error::ErrorKind;
+    use crate::services::audio_source::BufferSource;
+    #[cfg(feature = "audio")]
+    use cpal::Sample;
+    #[cfg(feature = "audio")]
+    use ringbuf::HeapRb;
     use tokio::sync::broadcast::error::TryRecvError;
 
     #[test]
@@ -259,18 +620,19 @@ mod tests {
     fn test_sample_format_conversion() {
         // Test f32 to f32 conversion
         let f32_sample: f32 = 0.5;
-        let converted: f32 = f32_sample.into();
-        assert_eq!(converted, 0.5);
+        assert_eq!(f32_sample, 0.5);
 
         // Test i16 to f32 conversion (normalized)
         let i16_sample: i16 = 16384; // Half of i16::MAX
-        let converted: f32 = i16_sample.into();
-        assert!((converted - 0.5).abs() < 0.001);
-
-        // Test u16 to f32 conversion (normalized)
-        let u16_sample: u16 = 32768; // Half of u16::MAX
-        let converted: f32 = u16_sample.into();
-        assert!((converted - 0.5).abs() < 0.001);
+        assert!((i16_sample.to_sample::<f32>() - 0.5).abs() < 0.001);
+        assert_eq!(i16::MIN.to_sample::<f32>(), -1.0);
+        assert!(i16::MAX.to_sample::<f32>() <= 1.0);
+
+        // Test u16 to f32 conversion (normalized, centred on 32768)
+        let u16_sample: u16 = 32768;
+        assert_eq!(u16_sample.to_sample::<f32>(), 0.0);
+        assert_eq!(u16::MIN.to_sample::<f32>(), -1.0);
+        assert!((49152u16.to_sample::<f32>() - 0.5).abs() < 0.001);
     }
 
     #[cfg(feature = "audio")]
@@ -412,14 +774,12 @@ mod tests {
         // Test that Arc<Mutex<>> works correctly
         let service = AudioService::new();
         let recording_state = service.is_recording.clone();
-        #[cfg(feature = "tauri")]
-        let app_handle_state = service.app_handle.clone();
+        let events_state = service.events.clone();
 
         // Test that we can access the state from different references
         {
             let _guard1 = recording_state.lock().unwrap();
-            #[cfg(feature = "tauri")]
-            let _guard2 = app_handle_state.lock().unwrap();
+            let _guard2 = events_state.lock().unwrap();
             // Both locks should work simultaneously since they're different mutexes
         }
 
@@ -428,6 +788,66 @@ mod tests {
         assert!(!moved_service.is_recording());
     }
 
+    #[test]
+    fn test_match_device_name() {
+        let available = vec!["Built-in Microphone".to_string(), "USB Headset".to_string()];
+
+        assert_eq!(match_device_name(&available, "USB Headset"), Some(1));
+        assert_eq!(match_device_name(&available, "usb headset "), Some(1));
+        assert_eq!(match_device_name(&available, "Bluetooth Headset"), None);
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_require_recording() {
+        let service = AudioService::new();
+
+        assert_eq!(service.pause_recording().await.unwrap_err().kind, ErrorKind::Validation);
+        assert_eq!(service.resume_recording().await.unwrap_err().kind, ErrorKind::Validation);
+        assert!(!service.is_paused());
+    }
+
+    #[test]
+    fn test_audio_meter_is_shared_between_clones() {
+        let service = AudioService::new();
+        assert_eq!(service.audio_level(), 0.0);
+
+        // The capture callback updates the same meter the commands read
+        service.meter.lock().unwrap().update(&[0.5, -0.5]);
+        let cloned = service.clone();
+        assert_eq!(cloned.audio_level(), 0.5);
+        assert_eq!(cloned.audio_meter().peak, 0.5);
+
+        service.meter.lock().unwrap().reset();
+        assert_eq!(cloned.audio_meter(), AudioMeterReading::default());
+    }
+
+    #[test]
+    fn test_capture_worker_exits_when_dropped() {
+        let source = BufferSource::new(vec![0.0; 160], SourceFormat { sample_rate: 16_000, channels: 1 });
+        let worker = CaptureWorker::spawn(Arc::new(source)).unwrap();
+        assert!(worker.is_alive());
+
+        // Drop closes the command channel and joins the thread
+        drop(worker);
+    }
+
+    #[cfg(feature = "audio")]
+    #[tokio::test]
+    async fn test_repeated_start_stop_reuses_one_worker() {
+        let service = AudioService::new();
+
+        for _ in 0..5 {
+            // May fail without a microphone; either way the flags must settle
+            let _ = service.start_recording().await;
+            assert!(service.stop_recording().await.is_ok());
+            assert!(!service.is_recording());
+            assert!(!service.is_paused());
+        }
+
+        let capture = service.capture.lock().unwrap();
+        assert!(capture.as_ref().is_some_and(CaptureWorker::is_alive));
+    }
+
     #[test]
     fn test_broadcast_receiver_behavior() {
         let service = AudioService::new();
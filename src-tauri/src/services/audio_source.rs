@@ -0,0 +1,528 @@
+use crate::services::audio_frames::samples_per_frame;
+use crate::services::audio_service::{match_device_name, InputDeviceSelection};
+use crate::services::wav::decode_wav;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "audio")]
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+// Buffer sources hand out audio in chunks about this long, like a device callback
+const DEFAULT_CHUNK_MS: u32 = 10;
+// How often a paused buffer source checks whether to carry on
+const PAUSE_POLL: Duration = Duration::from_millis(5);
+const NOISE_SEED: u64 = 0x1111_e5ee;
+
+/// Interleaved samples in [-1.0, 1.0], delivered from the source's own thread.
+pub type SampleCallback = Box<dyn FnMut(&[f32]) + Send>;
+
+/// Builds the sample callback once the source knows what it will deliver.
+pub type CallbackFactory = Box<dyn FnOnce(SourceFormat) -> SampleCallback + Send>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SourceFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// A running capture. Dropping it stops delivery and releases the input.
+pub trait CaptureStream {
+    fn pause(&self) -> Result<(), String>;
+    fn resume(&self) -> Result<(), String>;
+}
+
+pub struct OpenedSource {
+    pub stream: Box<dyn CaptureStream>,
+    pub format: SourceFormat,
+    pub selection: InputDeviceSelection,
+}
+
+/// Where `AudioService` gets captured audio from. `open` runs on the capture
+/// thread, which also owns the returned stream (cpal streams are not `Send`).
+pub trait AudioSource: Send + Sync {
+    fn list_devices(&self) -> Result<Vec<String>, String>;
+
+    /// Opens the named input (or the default) and starts delivering samples.
+    fn open(&self, device_name: Option<&str>, on_open: CallbackFactory) -> Result<OpenedSource, String>;
+}
+
+/// The system's input devices, via cpal.
+#[cfg(feature = "audio")]
+#[derive(Default)]
+pub struct CpalSource;
+
+#[cfg(feature = "audio")]
+impl AudioSource for CpalSource {
+    fn list_devices(&self) -> Result<Vec<String>, String> {
+        let host = cpal::default_host();
+        let devices = host.input_devices()
+            .map_err(|e| format!("Failed to get input devices: {}", e))?
+            .filter_map(|device| device.name().ok())
+            .collect::<Vec<_>>();
+        Ok(devices)
+    }
+
+    fn open(&self, device_name: Option<&str>, on_open: CallbackFactory) -> Result<OpenedSource, String> {
+        let host = cpal::default_host();
+        let (device, selection) = select_input_device(&host, device_name)?;
+
+        let config = device.default_input_config()
+            .map_err(|e| format!("Failed to get default input config: {}", e))?;
+
+        log::info!("Audio device: {}", device.name().unwrap_or("Unknown".to_string()));
+        log::info!("Audio config: {:?}, sample rate: {}", config.sample_format(), config.sample_rate().0);
+
+        let format = SourceFormat {
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+        };
+        let callback = on_open(format);
+
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => build_input_stream::<f32>(&device, config.into(), callback)?,
+            cpal::SampleFormat::I16 => build_input_stream::<i16>(&device, config.into(), callback)?,
+            cpal::SampleFormat::U16 => build_input_stream::<u16>(&device, config.into(), callback)?,
+            _ => return Err(format!("Unsupported sample format: {:?}", config.sample_format())),
+        };
+
+        stream.play().map_err(|e| format!("Failed to start stream: {}", e))?;
+        Ok(OpenedSource {
+            stream: Box::new(CpalCapture(stream)),
+            format,
+            selection,
+        })
+    }
+}
+
+#[cfg(feature = "audio")]
+struct CpalCapture(cpal::Stream);
+
+#[cfg(feature = "audio")]
+impl CaptureStream for CpalCapture {
+    fn pause(&self) -> Result<(), String> {
+        self.0.pause().map_err(|e| format!("Failed to pause stream: {}", e))
+    }
+
+    fn resume(&self) -> Result<(), String> {
+        self.0.play().map_err(|e| format!("Failed to resume stream: {}", e))
+    }
+}
+
+#[cfg(feature = "audio")]
+fn select_input_device(host: &cpal::Host, requested: Option<&str>) -> Result<(cpal::Device, InputDeviceSelection), String> {
+    if let Some(name) = requested {
+        let mut devices = host.input_devices()
+            .map_err(|e| format!("Failed to get input devices: {}", e))?
+            .filter_map(|device| device.name().ok().map(|device_name| (device_name, device)))
+            .collect::<Vec<_>>();
+        let names = devices.iter().map(|(device_name, _)| device_name.clone()).collect::<Vec<_>>();
+
+        if let Some(index) = match_device_name(&names, name) {
+            let (device_name, device) = devices.swap_remove(index);
+            let selection = InputDeviceSelection {
+                device: device_name,
+                requested: Some(name.to_string()),
+                fell_back: false,
+            };
+            return Ok((device, selection));
+        }
+
+        log::warn!("Input device '{}' is not available, falling back to the default input device", name);
+    }
+
+    let device = host.default_input_device()
+        .ok_or("No default input device found")?;
+    let selection = InputDeviceSelection {
+        device: device.name().unwrap_or("Unknown".to_string()),
+        requested: requested.map(str::to_string),
+        fell_back: requested.is_some(),
+    };
+    Ok((device, selection))
+}
+
+#[cfg(feature = "audio")]
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: cpal::StreamConfig,
+    mut callback: SampleCallback,
+) -> Result<cpal::Stream, String>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    device.build_input_stream(
+        &config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            callback(&to_f32_samples(data));
+        },
+        move |err| {
+            log::error!("Audio stream error: {}", err);
+        },
+        None,
+    ).map_err(|e| format!("Failed to build input stream: {}", e))
+}
+
+// Integer formats are scaled into [-1.0, 1.0] (u16 is also re-centred on zero)
+#[cfg(feature = "audio")]
+fn to_f32_samples<T>(data: &[T]) -> Vec<f32>
+where
+    T: cpal::Sample,
+    f32: cpal::FromSample<T>,
+{
+    data.iter().map(|&sample| sample.to_sample::<f32>()).collect()
+}
+
+/// Plays an in-memory recording through the capture path, in device-sized
+/// chunks from a thread of its own. The stream ends when the buffer runs out.
+#[derive(Clone)]
+pub struct BufferSource {
+    name: String,
+    samples: Arc<Vec<f32>>,
+    format: SourceFormat,
+    chunk_ms: u32,
+    speed: Option<f32>,
+}
+
+impl BufferSource {
+    /// `samples` are interleaved in `format`.
+    pub fn new(samples: Vec<f32>, format: SourceFormat) -> Self {
+        Self {
+            name: "Memory".to_string(),
+            samples: Arc::new(samples),
+            format: SourceFormat {
+                sample_rate: format.sample_rate.max(1),
+                channels: format.channels.max(1),
+            },
+            chunk_ms: DEFAULT_CHUNK_MS,
+            speed: None,
+        }
+    }
+
+    /// Device name the source reports (and answers to).
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_chunk_ms(mut self, chunk_ms: u32) -> Self {
+        self.chunk_ms = chunk_ms.max(1);
+        self
+    }
+
+    /// Delivers at `speed` times real time (1.0 behaves like a microphone).
+    /// Unpaced sources deliver as fast as the callback takes the audio.
+    pub fn paced(mut self, speed: f32) -> Self {
+        self.speed = (speed > 0.0).then_some(speed);
+        self
+    }
+
+    pub fn format(&self) -> SourceFormat {
+        self.format
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        let frames = self.samples.len() as u64 / self.format.channels as u64;
+        frames * 1000 / self.format.sample_rate as u64
+    }
+
+    // Appends `duration_ms` of audio, the same value on every channel
+    fn extend(&mut self, duration_ms: u32, mut sample: impl FnMut(usize) -> f32) {
+        let frames = samples_per_frame(self.format.sample_rate, duration_ms);
+        let channels = self.format.channels as usize;
+        let samples = Arc::make_mut(&mut self.samples);
+        samples.reserve(frames * channels);
+        for index in 0..frames {
+            let value = sample(index);
+            samples.extend(std::iter::repeat_n(value, channels));
+        }
+    }
+}
+
+impl AudioSource for BufferSource {
+    fn list_devices(&self) -> Result<Vec<String>, String> {
+        Ok(vec![self.name.clone()])
+    }
+
+    fn open(&self, device_name: Option<&str>, on_open: CallbackFactory) -> Result<OpenedSource, String> {
+        let selection = InputDeviceSelection {
+            device: self.name.clone(),
+            requested: device_name.map(str::to_string),
+            fell_back: device_name.is_some_and(|name| match_device_name(std::slice::from_ref(&self.name), name).is_none()),
+        };
+
+        let mut callback = on_open(self.format);
+        let control = Arc::new(FeedControl::default());
+        let chunk_len = samples_per_frame(self.format.sample_rate, self.chunk_ms) * self.format.channels as usize;
+        let interval = self.speed.map(|speed| Duration::from_secs_f32(self.chunk_ms as f32 / 1000.0 / speed));
+        let samples = self.samples.clone();
+
+        let feed = control.clone();
+        let thread = thread::Builder::new()
+            .name("lily-audio-source".to_string())
+            .spawn(move || {
+                for chunk in samples.chunks(chunk_len) {
+                    while feed.paused.load(Ordering::Acquire) && !feed.stopped.load(Ordering::Acquire) {
+                        thread::sleep(PAUSE_POLL);
+                    }
+                    if feed.stopped.load(Ordering::Acquire) {
+                        break;
+                    }
+                    callback(chunk);
+                    if let Some(interval) = interval {
+                        thread::sleep(interval);
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to spawn audio source thread: {}", e))?;
+
+        Ok(OpenedSource {
+            stream: Box::new(BufferCapture { control, thread: Some(thread) }),
+            format: self.format,
+            selection,
+        })
+    }
+}
+
+#[derive(Default)]
+struct FeedControl {
+    paused: AtomicBool,
+    stopped: AtomicBool,
+}
+
+struct BufferCapture {
+    control: Arc<FeedControl>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl CaptureStream for BufferCapture {
+    fn pause(&self) -> Result<(), String> {
+        self.control.paused.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    fn resume(&self) -> Result<(), String> {
+        self.control.paused.store(false, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Drop for BufferCapture {
+    fn drop(&mut self) {
+        self.control.stopped.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A WAV file played through the capture path as if it were a microphone.
+pub struct WavFileSource {
+    buffer: BufferSource,
+}
+
+impl WavFileSource {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| format!("Failed to read WAV file {}: {}", path.display(), e))?;
+        let audio = decode_wav(&data)?;
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+
+        let format = SourceFormat {
+            sample_rate: audio.sample_rate,
+            channels: audio.channels,
+        };
+        Ok(Self {
+            buffer: BufferSource::new(audio.samples, format).with_name(name),
+        })
+    }
+
+    pub fn paced(mut self, speed: f32) -> Self {
+        self.buffer = self.buffer.paced(speed);
+        self
+    }
+
+    pub fn format(&self) -> SourceFormat {
+        self.buffer.format()
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        self.buffer.duration_ms()
+    }
+}
+
+impl AudioSource for WavFileSource {
+    fn list_devices(&self) -> Result<Vec<String>, String> {
+        self.buffer.list_devices()
+    }
+
+    fn open(&self, device_name: Option<&str>, on_open: CallbackFactory) -> Result<OpenedSource, String> {
+        self.buffer.open(device_name, on_open)
+    }
+}
+
+/// Generated test signal built from tone, noise and silence segments, e.g.
+/// silence, then "speech", then silence again to drive voice detection.
+pub struct SyntheticSource {
+    buffer: BufferSource,
+    rng: StdRng,
+}
+
+impl SyntheticSource {
+    pub fn new(format: SourceFormat) -> Self {
+        Self {
+            buffer: BufferSource::new(Vec::new(), format).with_name("Synthetic"),
+            rng: StdRng::seed_from_u64(NOISE_SEED),
+        }
+    }
+
+    /// Appends a sine wave with the given peak amplitude.
+    pub fn tone(mut self, frequency: f32, amplitude: f32, duration_ms: u32) -> Self {
+        let step = 2.0 * std::f32::consts::PI * frequency / self.buffer.format.sample_rate as f32;
+        self.buffer.extend(duration_ms, |index| amplitude * (index as f32 * step).sin());
+        self
+    }
+
+    /// Appends uniform white noise with the given peak amplitude (seeded, so repeatable).
+    pub fn noise(mut self, amplitude: f32, duration_ms: u32) -> Self {
+        let rng = &mut self.rng;
+        self.buffer.extend(duration_ms, |_| rng.gen_range(-amplitude..=amplitude));
+        self
+    }
+
+    pub fn silence(mut self, duration_ms: u32) -> Self {
+        self.buffer.extend(duration_ms, |_| 0.0);
+        self
+    }
+
+    pub fn paced(mut self, speed: f32) -> Self {
+        self.buffer = self.buffer.paced(speed);
+        self
+    }
+
+    pub fn format(&self) -> SourceFormat {
+        self.buffer.format()
+    }
+
+    pub fn duration_ms(&self) -> u64 {
+        self.buffer.duration_ms()
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        self.buffer.samples()
+    }
+}
+
+impl AudioSource for SyntheticSource {
+    fn list_devices(&self) -> Result<Vec<String>, String> {
+        self.buffer.list_devices()
+    }
+
+    fn open(&self, device_name: Option<&str>, on_open: CallbackFactory) -> Result<OpenedSource, String> {
+        self.buffer.open(device_name, on_open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::audio_meter::rms;
+    use crate::services::wav::encode_wav;
+    use std::sync::mpsc;
+
+    const FORMAT: SourceFormat = SourceFormat { sample_rate: 16_000, channels: 2 };
+
+    // Opens `source` and collects everything it delivers until it runs out
+    fn drain(source: &dyn AudioSource) -> Vec<Vec<f32>> {
+        let (tx, rx) = mpsc::channel();
+        let opened = source
+            .open(None, Box::new(move |_| Box::new(move |samples: &[f32]| {
+                let _ = tx.send(samples.to_vec());
+            })))
+            .unwrap();
+        let chunks = rx.iter().collect();
+        drop(opened);
+        chunks
+    }
+
+    #[test]
+    fn test_synthetic_segments_have_expected_length_and_level() {
+        let source = SyntheticSource::new(FORMAT).silence(100).tone(440.0, 0.5, 200).noise(0.2, 50);
+        assert_eq!(source.duration_ms(), 350);
+        assert_eq!(source.samples().len(), 16 * 350 * 2);
+
+        // Channels carry the same signal
+        let tone = &source.samples()[16 * 100 * 2..16 * 300 * 2];
+        assert!(tone.chunks(2).all(|frame| frame[0] == frame[1]));
+        assert!((rms(tone) - 0.5 / 2f32.sqrt()).abs() < 0.01);
+        assert!(source.samples()[..16 * 100 * 2].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_buffer_source_delivers_everything_in_chunks() {
+        let samples: Vec<f32> = (0..1000).map(|i| i as f32 / 1000.0).collect();
+        let source = BufferSource::new(samples.clone(), SourceFormat { sample_rate: 8_000, channels: 1 });
+
+        let chunks = drain(&source);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 80)); // 10 ms
+        assert_eq!(chunks.concat(), samples);
+    }
+
+    #[test]
+    fn test_buffer_source_reports_device_selection() {
+        let source = BufferSource::new(vec![0.0; 10], FORMAT).with_name("Test Mic");
+        assert_eq!(source.list_devices().unwrap(), vec!["Test Mic".to_string()]);
+
+        let opened = source.open(Some("test mic"), Box::new(|_| Box::new(|_: &[f32]| {}))).unwrap();
+        assert!(!opened.selection.fell_back);
+        let opened = source.open(Some("USB Headset"), Box::new(|_| Box::new(|_: &[f32]| {}))).unwrap();
+        assert!(opened.selection.fell_back);
+        assert_eq!(opened.selection.device, "Test Mic");
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_integer_device_samples_stay_normalized() {
+        // What an i16 microphone hands the capture callback: full scale both ways, then half scale
+        let device: Vec<i16> = (0..1600).map(|i| if i % 2 == 0 { i16::MAX } else { i16::MIN }).chain([16384, -16384]).collect();
+        let source = BufferSource::new(to_f32_samples(&device), SourceFormat { sample_rate: 16_000, channels: 1 });
+
+        let samples = drain(&source).concat();
+        assert_eq!(samples.len(), device.len());
+        assert!(samples.iter().all(|sample| (-1.0..=1.0).contains(sample)));
+        assert!((samples[1600] - 0.5).abs() < 0.001);
+        assert!((samples[1601] + 0.5).abs() < 0.001);
+
+        // u16 devices are centred on 32768
+        let unsigned = to_f32_samples(&[0u16, 32768, u16::MAX]);
+        assert_eq!(unsigned[..2], [-1.0, 0.0]);
+        assert!(unsigned[2] <= 1.0);
+    }
+
+    #[test]
+    fn test_wav_file_source_reads_file() {
+        let path = std::env::temp_dir().join(format!("lily-source-{}.wav", std::process::id()));
+        std::fs::write(&path, encode_wav(&[0.25; 4800], 48_000, 1)).unwrap();
+
+        let source = WavFileSource::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(source.format(), SourceFormat { sample_rate: 48_000, channels: 1 });
+        assert_eq!(source.duration_ms(), 100);
+
+        let samples = drain(&source).concat();
+        assert_eq!(samples.len(), 4800);
+        assert!(samples.iter().all(|&s| (s - 0.25).abs() < 1e-3));
+
+        assert!(WavFileSource::open(std::env::temp_dir().join("lily-missing.wav")).is_err());
+    }
+}
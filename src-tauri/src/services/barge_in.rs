@@ -0,0 +1,155 @@
+use crate::services::audio_playback_service::AudioPlaybackService;
+use crate::services::vad::VadTransition;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BargeInMode {
+    /// Drop the current reply and everything queued behind it.
+    Stop,
+    /// Lower playback while the user talks, restoring it when they stop.
+    Duck,
+}
+
+// Persisted under `AppSettings.audio.barge_in`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct BargeInSettings {
+    pub enabled: bool,
+    pub mode: BargeInMode,
+    /// Playback gain while ducked, 0.0 to 1.0.
+    pub duck_gain: f32,
+}
+
+impl Default for BargeInSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mode: BargeInMode::Stop,
+            duck_gain: 0.2,
+        }
+    }
+}
+
+/// Payload of the `barge-in` event.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BargeInEvent {
+    pub mode: BargeInMode,
+    /// The reply that was playing when the user started talking.
+    pub playback_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BargeInAction {
+    Stop,
+    Duck(f32),
+    Restore,
+}
+
+/// Decides what speech transitions mean for TTS playback.
+#[derive(Default)]
+pub struct BargeIn {
+    settings: BargeInSettings,
+    ducked: bool,
+}
+
+impl BargeIn {
+    pub fn new(settings: BargeInSettings) -> Self {
+        Self { settings, ducked: false }
+    }
+
+    pub fn set_settings(&mut self, settings: BargeInSettings) {
+        self.settings = settings;
+    }
+
+    pub fn on_speech(&mut self, transition: VadTransition, playback_active: bool) -> Option<BargeInAction> {
+        match transition {
+            VadTransition::SpeechStart if self.settings.enabled && playback_active => match self.settings.mode {
+                BargeInMode::Stop => Some(BargeInAction::Stop),
+                BargeInMode::Duck => {
+                    self.ducked = true;
+                    Some(BargeInAction::Duck(self.settings.duck_gain))
+                }
+            },
+            // Undo a duck even if playback finished meanwhile, so the next reply isn't quiet
+            VadTransition::SpeechEnd { .. } if self.ducked => {
+                self.ducked = false;
+                Some(BargeInAction::Restore)
+            }
+            _ => None,
+        }
+    }
+
+    /// Applies a decision to playback. Returns the event to report for an
+    /// actual barge-in (not for restoring a duck).
+    pub fn apply(&self, action: BargeInAction, playback: &AudioPlaybackService) -> Option<BargeInEvent> {
+        let playback_id = playback.status().playing;
+        match action {
+            BargeInAction::Stop => {
+                playback.stop();
+                Some(BargeInEvent { mode: BargeInMode::Stop, playback_id })
+            }
+            BargeInAction::Duck(gain) => {
+                playback.set_duck(Some(gain));
+                Some(BargeInEvent { mode: BargeInMode::Duck, playback_id })
+            }
+            BargeInAction::Restore => {
+                playback.set_duck(None);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const END: VadTransition = VadTransition::SpeechEnd { duration_ms: 500 };
+
+    #[test]
+    fn test_speech_without_playback_is_ignored() {
+        let mut barge_in = BargeIn::default();
+        assert_eq!(barge_in.on_speech(VadTransition::SpeechStart, false), None);
+        assert_eq!(barge_in.on_speech(END, false), None);
+    }
+
+    #[test]
+    fn test_stop_mode_stops_on_speech_start() {
+        let mut barge_in = BargeIn::default();
+        assert_eq!(barge_in.on_speech(VadTransition::SpeechStart, true), Some(BargeInAction::Stop));
+        assert_eq!(barge_in.on_speech(END, true), None);
+    }
+
+    #[test]
+    fn test_duck_mode_restores_on_speech_end() {
+        let settings = BargeInSettings { mode: BargeInMode::Duck, ..BargeInSettings::default() };
+        let mut barge_in = BargeIn::new(settings);
+
+        assert_eq!(barge_in.on_speech(VadTransition::SpeechStart, true), Some(BargeInAction::Duck(0.2)));
+        assert_eq!(barge_in.on_speech(END, false), Some(BargeInAction::Restore));
+        assert_eq!(barge_in.on_speech(END, false), None);
+    }
+
+    #[test]
+    fn test_disabled_barge_in_does_nothing() {
+        let settings = BargeInSettings { enabled: false, ..BargeInSettings::default() };
+        let mut barge_in = BargeIn::new(settings);
+        assert_eq!(barge_in.on_speech(VadTransition::SpeechStart, true), None);
+    }
+
+    #[test]
+    fn test_apply_updates_playback() {
+        let playback = AudioPlaybackService::new();
+        let barge_in = BargeIn::default();
+
+        assert_eq!(
+            barge_in.apply(BargeInAction::Duck(0.3), &playback),
+            Some(BargeInEvent { mode: BargeInMode::Duck, playback_id: None })
+        );
+        assert!(playback.status().ducked);
+
+        assert_eq!(barge_in.apply(BargeInAction::Restore, &playback), None);
+        assert!(!playback.status().ducked);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::domain::error::LilyError;
+use crate::infrastructure::chat_stream::ChatStreamEvent;
+use crate::infrastructure::lily_core::{ChatRequest, ChatResponse, LilyCoreClient};
+use crate::services::events::EventSink;
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::task::AbortHandle;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChatDelta {
+    pub message_id: String,
+    pub delta: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChatComplete {
+    pub message_id: String,
+    pub response: String,
+    pub timestamp: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChatFailed {
+    pub message_id: String,
+    pub error: LilyError,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChatCancelled {
+    pub message_id: String,
+}
+
+// A request's entry in `ChatService::in_flight`. Dropping it removes the entry
+// and aborts the request, so a caller that goes away (e.g. a cancelled command
+// future) doesn't leave it behind.
+struct InFlightEntry {
+    in_flight: Arc<Mutex<HashMap<String, AbortHandle>>>,
+    request_id: String,
+}
+
+impl InFlightEntry {
+    // Removes the entry; false if `cancel` got there first
+    fn finish(&self) -> bool {
+        self.in_flight.lock().unwrap().remove(&self.request_id).is_some()
+    }
+}
+
+impl Drop for InFlightEntry {
+    fn drop(&mut self) {
+        if let Some(task) = self.in_flight.lock().unwrap().remove(&self.request_id) {
+            task.abort();
+        }
+    }
+}
+
+/// Sends chat requests to Lily-Core and tracks them by id so they can be
+/// cancelled. Streamed replies arrive as `chat-delta` events and end with
+/// exactly one of `chat-complete`, `chat-error` or `chat-cancelled`.
+/// Clones share the set of requests in flight.
+#[derive(Clone)]
+pub struct ChatService {
+    client: LilyCoreClient,
+    events: Arc<dyn EventSink>,
+    in_flight: Arc<Mutex<HashMap<String, AbortHandle>>>,
+}
+
+impl ChatService {
+    pub fn new(client: LilyCoreClient, events: Arc<dyn EventSink>) -> Self {
+        Self {
+            client,
+            events,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Waits for the whole reply. Fails with `ErrorKind::Cancelled` if
+    /// `cancel` is called with `request_id` first.
+    pub async fn send(&self, request_id: String, request: ChatRequest) -> Result<ChatResponse, LilyError> {
+        let request = request.with_request_id(request_id.clone());
+        let client = self.client.clone();
+        let task = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if in_flight.contains_key(&request_id) {
+                return Err(LilyError::validation(format!("Chat request {} is already in flight", request_id)));
+            }
+            let task = tokio::spawn(async move { client.send_chat(&request).await });
+            in_flight.insert(request_id.clone(), task.abort_handle());
+            task
+        };
+        let entry = self.entry(&request_id);
+
+        let result = task.await;
+        // Gone from the map means `cancel` won, even if the reply arrived meanwhile
+        if !entry.finish() {
+            return Err(LilyError::cancelled("Chat request cancelled")
+                .with_details(serde_json::json!({ "request_id": request_id })));
+        }
+        result.map_err(|e| LilyError::protocol(format!("Chat request {} failed: {}", request_id, e)))?
+    }
+
+    /// Starts streaming the reply in the background and returns its message id,
+    /// which is also the id to cancel it with.
+    pub fn stream(&self, request: ChatRequest) -> String {
+        let message_id = uuid::Uuid::new_v4().to_string();
+        let request = request.with_request_id(message_id.clone());
+        let service = self.clone();
+        let id = message_id.clone();
+
+        // Registered under the lock so the task can't finish before it is tracked
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let task = tokio::spawn(async move { service.run(id, request).await });
+        in_flight.insert(message_id.clone(), task.abort_handle());
+
+        message_id
+    }
+
+    /// Aborts a request in flight and tells Lily-Core to stop working on it.
+    /// Returns false if it already ended.
+    pub fn cancel(&self, request_id: &str) -> bool {
+        let Some(task) = self.in_flight.lock().unwrap().remove(request_id) else {
+            return false;
+        };
+        task.abort();
+        info!("Chat request {} cancelled", request_id);
+        self.emit("chat-cancelled", ChatCancelled { message_id: request_id.to_string() });
+
+        // Best effort: older Lily-Core versions have no cancel endpoint
+        let client = self.client.clone();
+        let id = request_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = client.cancel_chat(&id).await {
+                debug!("Lily-Core did not accept the cancel notice for {}: {}", id, e);
+            }
+        });
+        true
+    }
+
+    pub fn is_in_flight(&self, request_id: &str) -> bool {
+        self.in_flight.lock().unwrap().contains_key(request_id)
+    }
+
+    async fn run(&self, message_id: String, request: ChatRequest) {
+        let entry = self.entry(&message_id);
+        let result = async {
+            let mut stream = self.client.stream_chat(&request).await?;
+            while let Some(event) = stream.next().await {
+                match event? {
+                    ChatStreamEvent::Delta(delta) => {
+                        self.emit("chat-delta", ChatDelta { message_id: message_id.clone(), delta });
+                    }
+                    ChatStreamEvent::Done(response) => return Ok(response),
+                    ChatStreamEvent::Error(message) => {
+                        return Err(LilyError::protocol(message).with_details(serde_json::json!({ "source": "lily-core" })));
+                    }
+                }
+            }
+            Err(LilyError::protocol("Chat stream ended without a reply"))
+        }
+        .await;
+
+        // A cancelled request has already reported its end
+        if !entry.finish() {
+            return;
+        }
+        match result {
+            Ok(response) => self.emit(
+                "chat-complete",
+                ChatComplete { message_id, response: response.response, timestamp: response.timestamp },
+            ),
+            Err(error) => {
+                warn!("Chat request {} failed: {}", message_id, error);
+                self.emit("chat-error", ChatFailed { message_id, error });
+            }
+        }
+    }
+
+    fn entry(&self, request_id: &str) -> InFlightEntry {
+        InFlightEntry { in_flight: self.in_flight.clone(), request_id: request_id.to_string() }
+    }
+
+    fn emit<T: Serialize>(&self, event: &str, payload: T) {
+        if let Err(e) = self.events.emit(event, payload) {
+            warn!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::error::ErrorKind;
+    use crate::domain::models::ServerSettings;
+    use crate::services::events::{ChannelEventSink, EmittedEvent};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc::UnboundedReceiver;
+    use tokio::time::{timeout, Duration};
+
+    // Streams `chunks` as a chunked SSE body, then holds the connection open if `hang`
+    async fn serve_stream(chunks: &'static [&'static str], hang: bool) -> LilyCoreClient {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 4096];
+            let _ = stream.read(&mut buffer).await;
+            let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ntransfer-encoding: chunked\r\n\r\n";
+            stream.write_all(head.as_bytes()).await.unwrap();
+            for chunk in chunks {
+                let frame = format!("{:x}\r\n{}\r\n", chunk.len(), chunk);
+                stream.write_all(frame.as_bytes()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            if hang {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            }
+            let _ = stream.write_all(b"0\r\n\r\n").await;
+        });
+
+        LilyCoreClient::new().with_server_settings(ServerSettings {
+            http_base_url: format!("http://{}", address),
+            ..ServerSettings::default()
+        })
+    }
+
+    async fn next_event(rx: &mut UnboundedReceiver<EmittedEvent>) -> EmittedEvent {
+        timeout(Duration::from_secs(5), rx.recv()).await.expect("timed out waiting for event").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stream_emits_deltas_then_complete() {
+        let client = serve_stream(&["data: {\"delta\":\"Hi\"}\n\n", "data: {\"delta\":\" there\"}\n\n", "data: [DONE]\n\n"], false).await;
+        let (sink, mut rx) = ChannelEventSink::new();
+        let chat = ChatService::new(client, Arc::new(sink));
+
+        let id = chat.stream(ChatRequest::new("hello".to_string(), None));
+
+        let first = next_event(&mut rx).await;
+        assert_eq!(first.name, "chat-delta");
+        assert_eq!(first.payload, serde_json::json!({ "message_id": id, "delta": "Hi" }));
+        assert_eq!(next_event(&mut rx).await.payload["delta"], " there");
+
+        let complete = next_event(&mut rx).await;
+        assert_eq!(complete.name, "chat-complete");
+        assert_eq!(complete.payload["message_id"], id.as_str());
+        assert_eq!(complete.payload["response"], "Hi there");
+        assert!(!chat.is_in_flight(&id));
+        assert!(!chat.cancel(&id));
+    }
+
+    #[tokio::test]
+    async fn test_server_error_becomes_chat_error() {
+        let client = serve_stream(&["event: error\ndata: {\"error\":\"agent crashed\"}\n\n"], false).await;
+        let (sink, mut rx) = ChannelEventSink::new();
+        let chat = ChatService::new(client, Arc::new(sink));
+
+        chat.stream(ChatRequest::new("hello".to_string(), None));
+
+        let error = next_event(&mut rx).await;
+        assert_eq!(error.name, "chat-error");
+        assert_eq!(error.payload["error"]["kind"], "protocol");
+        assert_eq!(error.payload["error"]["message"], "agent crashed");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_a_hung_stream() {
+        let client = serve_stream(&["data: {\"delta\":\"thinking\"}\n\n"], true).await;
+        let (sink, mut rx) = ChannelEventSink::new();
+        let chat = ChatService::new(client, Arc::new(sink));
+
+        let id = chat.stream(ChatRequest::new("hello".to_string(), None));
+        assert_eq!(next_event(&mut rx).await.name, "chat-delta");
+
+        assert!(chat.cancel(&id));
+        let cancelled = next_event(&mut rx).await;
+        assert_eq!(cancelled, EmittedEvent { name: "chat-cancelled".to_string(), payload: serde_json::json!({ "message_id": id }) });
+
+        // Nothing else arrives for a cancelled request
+        assert!(timeout(Duration::from_millis(200), rx.recv()).await.is_err());
+        assert!(!chat.cancel(&id));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_aborts_a_pending_send() {
+        let client = serve_stream(&[], true).await;
+        let (sink, mut rx) = ChannelEventSink::new();
+        let chat = ChatService::new(client, Arc::new(sink));
+
+        let pending = tokio::spawn({
+            let chat = chat.clone();
+            async move { chat.send("req-1".to_string(), ChatRequest::new("hello".to_string(), None)).await }
+        });
+        while !chat.is_in_flight("req-1") {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let duplicate = chat.send("req-1".to_string(), ChatRequest::new("again".to_string(), None)).await;
+        assert_eq!(duplicate.unwrap_err().kind, ErrorKind::Validation);
+
+        assert!(chat.cancel("req-1"));
+        let error = timeout(Duration::from_secs(5), pending).await.unwrap().unwrap().unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Cancelled);
+        assert_eq!(error.details, Some(serde_json::json!({ "request_id": "req-1" })));
+        assert_eq!(next_event(&mut rx).await.name, "chat-cancelled");
+        assert!(!chat.is_in_flight("req-1"));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_send_is_forgotten() {
+        let client = serve_stream(&[], true).await;
+        let (sink, _rx) = ChannelEventSink::new();
+        let chat = ChatService::new(client, Arc::new(sink));
+
+        // The caller gives up, e.g. the command future is dropped with the webview
+        let send = chat.send("req-1".to_string(), ChatRequest::new("hello".to_string(), None));
+        assert!(timeout(Duration::from_millis(100), send).await.is_err());
+
+        assert!(!chat.is_in_flight("req-1"));
+        assert!(!chat.cancel("req-1"));
+    }
+}
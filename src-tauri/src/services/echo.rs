@@ -0,0 +1,302 @@
+use crate::services::audio_meter::rms;
+use crate::services::audio_pipeline::LinearResampler;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// Coarse lag search runs on a decimated signal, then refines at full rate
+const DECIMATION: usize = 4;
+// Below this the speaker is effectively silent and there is nothing to suppress
+const REFERENCE_FLOOR_RMS: f32 = 0.003;
+
+// Persisted under `AppSettings.audio.echo`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct EchoSettings {
+    pub enabled: bool,
+    /// Normalised correlation with the playback signal above which a frame is treated as echo.
+    pub correlation_threshold: f32,
+    /// Longest speaker-to-microphone delay searched for.
+    pub max_delay_ms: u32,
+    /// Gain applied to echo frames; 0.0 mutes them.
+    pub suppression_gain: f32,
+}
+
+impl Default for EchoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            correlation_threshold: 0.5,
+            max_delay_ms: 400,
+            suppression_gain: 0.0,
+        }
+    }
+}
+
+struct ReferenceBuffer {
+    sample_rate: u32,
+    capacity: usize,
+    samples: VecDeque<f32>,
+    // Keyed by input rate so a device change gets a fresh resampler
+    resampler: Option<(u32, LinearResampler)>,
+}
+
+impl ReferenceBuffer {
+    fn new(sample_rate: u32, history_ms: u32) -> Self {
+        let capacity = (sample_rate as u64 * history_ms as u64 / 1000).max(1) as usize;
+        Self {
+            sample_rate,
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+            resampler: None,
+        }
+    }
+}
+
+/// Recent speaker output, at the capture stream rate, shared between the
+/// playback callback (writer) and the capture pipeline (reader).
+#[derive(Clone)]
+pub struct EchoReference {
+    inner: Arc<Mutex<ReferenceBuffer>>,
+}
+
+impl Default for EchoReference {
+    fn default() -> Self {
+        Self::new(16_000, 1000)
+    }
+}
+
+impl EchoReference {
+    pub fn new(sample_rate: u32, history_ms: u32) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ReferenceBuffer::new(sample_rate, history_ms))),
+        }
+    }
+
+    /// Matches the reference to a new capture format; clears the history.
+    pub fn configure(&self, sample_rate: u32, history_ms: u32) {
+        *self.inner.lock().unwrap() = ReferenceBuffer::new(sample_rate, history_ms);
+    }
+
+    /// Appends mono speaker output (silence included, to keep time aligned).
+    pub fn write(&self, mono: &[f32], input_rate: u32) {
+        let mut buffer = self.inner.lock().unwrap();
+        let target_rate = buffer.sample_rate;
+        let resampled = match &mut buffer.resampler {
+            Some((rate, resampler)) if *rate == input_rate => resampler.process(mono),
+            slot => {
+                let mut resampler = LinearResampler::new(input_rate, target_rate);
+                let resampled = resampler.process(mono);
+                *slot = Some((input_rate, resampler));
+                resampled
+            }
+        };
+
+        buffer.samples.extend(resampled);
+        let excess = buffer.samples.len().saturating_sub(buffer.capacity);
+        buffer.samples.drain(..excess);
+    }
+
+    /// The most recent `len` samples, oldest first (shorter if not yet filled).
+    pub fn latest(&self, len: usize) -> Vec<f32> {
+        let buffer = self.inner.lock().unwrap();
+        let start = buffer.samples.len().saturating_sub(len);
+        buffer.samples.range(start..).copied().collect()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.inner.lock().unwrap().sample_rate
+    }
+}
+
+fn decimate(samples: &[f32]) -> Vec<f32> {
+    samples
+        .chunks(DECIMATION)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect()
+}
+
+// Normalised cross-correlation of `signal` against `reference[lag..]`
+fn correlation_at(signal: &[f32], reference: &[f32], lag: usize) -> f32 {
+    let window = &reference[lag..lag + signal.len()];
+    let mut dot = 0.0f32;
+    let mut signal_energy = 0.0f32;
+    let mut reference_energy = 0.0f32;
+    for (&s, &r) in signal.iter().zip(window) {
+        dot += s * r;
+        signal_energy += s * s;
+        reference_energy += r * r;
+    }
+    let norm = (signal_energy * reference_energy).sqrt();
+    if norm <= f32::EPSILON {
+        0.0
+    } else {
+        (dot / norm).abs()
+    }
+}
+
+/// Highest normalised correlation between `signal` and any equally long
+/// window of `reference`. Searches coarsely on decimated signals, then
+/// refines around the best coarse lag.
+pub fn max_correlation(signal: &[f32], reference: &[f32]) -> f32 {
+    if signal.is_empty() || reference.len() < signal.len() {
+        return 0.0;
+    }
+
+    let coarse_signal = decimate(signal);
+    let coarse_reference = decimate(reference);
+    let coarse_lags = coarse_reference.len().saturating_sub(coarse_signal.len());
+    let best_coarse = (0..=coarse_lags)
+        .map(|lag| (lag, correlation_at(&coarse_signal, &coarse_reference, lag)))
+        .fold((0, 0.0f32), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+
+    let max_lag = reference.len() - signal.len();
+    let centre = best_coarse.0 * DECIMATION;
+    let start = centre.saturating_sub(DECIMATION);
+    let end = (centre + DECIMATION).min(max_lag);
+    (start..=end)
+        .map(|lag| correlation_at(signal, reference, lag))
+        .fold(0.0f32, f32::max)
+}
+
+/// Gated suppressor: frames that match what the speaker just played are
+/// attenuated before they reach voice detection and the server. When the
+/// user talks over playback the mix no longer correlates and passes through.
+pub struct EchoSuppressor {
+    settings: EchoSettings,
+    reference: EchoReference,
+}
+
+impl EchoSuppressor {
+    pub fn new(settings: EchoSettings, reference: EchoReference) -> Self {
+        Self { settings, reference }
+    }
+
+    /// Returns true if the frame was treated as echo.
+    pub fn process(&mut self, frame: &mut [f32]) -> bool {
+        if !self.settings.enabled || frame.is_empty() {
+            return false;
+        }
+
+        let delay = (self.reference.sample_rate() as u64 * self.settings.max_delay_ms as u64 / 1000) as usize;
+        let reference = self.reference.latest(frame.len() + delay);
+        if rms(&reference) < REFERENCE_FLOOR_RMS {
+            return false;
+        }
+
+        if max_correlation(frame, &reference) < self.settings.correlation_threshold {
+            return false;
+        }
+
+        for sample in frame.iter_mut() {
+            *sample *= self.settings.suppression_gain;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+    const FRAME: usize = 640;
+
+    // Deterministic white noise; different seeds are uncorrelated
+    fn noise(seed: u32, len: usize, amplitude: f32) -> Vec<f32> {
+        let mut state = seed.wrapping_mul(2_654_435_761).max(1);
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    // Plays `played` through the reference and returns the echo the mic would
+    // hear `delay` samples later, attenuated by the room
+    fn echo_of(played: &[f32], delay: usize, gain: f32) -> Vec<f32> {
+        let end = played.len() - delay;
+        played[end - FRAME..end].iter().map(|s| s * gain).collect()
+    }
+
+    fn suppressor_with(played: &[f32]) -> EchoSuppressor {
+        let reference = EchoReference::new(RATE, 1000);
+        reference.write(played, RATE);
+        EchoSuppressor::new(EchoSettings::default(), reference)
+    }
+
+    #[test]
+    fn test_correlation_finds_delayed_copy() {
+        let reference = noise(1, 4000, 0.5);
+        let copy = reference[1234..1234 + FRAME].to_vec();
+        assert!(max_correlation(&copy, &reference) > 0.99);
+        assert!(max_correlation(&noise(2, FRAME, 0.5), &reference) < 0.3);
+    }
+
+    #[test]
+    fn test_echo_frame_is_muted() {
+        let played = noise(1, 8000, 0.5);
+        let mut suppressor = suppressor_with(&played);
+
+        let mut frame = echo_of(&played, 1600, 0.3); // 100 ms acoustic delay
+        assert!(suppressor.process(&mut frame));
+        assert!(frame.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_uncorrelated_speech_passes_during_playback() {
+        let played = noise(1, 8000, 0.5);
+        let mut suppressor = suppressor_with(&played);
+
+        let mut frame = noise(7, FRAME, 0.4);
+        let original = frame.clone();
+        assert!(!suppressor.process(&mut frame));
+        assert_eq!(frame, original);
+    }
+
+    #[test]
+    fn test_double_talk_passes() {
+        let played = noise(1, 8000, 0.5);
+        let mut suppressor = suppressor_with(&played);
+
+        // The user talking louder than the echo breaks the correlation
+        let speech = noise(9, FRAME, 0.6);
+        let mut frame: Vec<f32> = echo_of(&played, 800, 0.1).iter().zip(&speech).map(|(e, s)| e + s).collect();
+        assert!(!suppressor.process(&mut frame));
+    }
+
+    #[test]
+    fn test_silent_reference_never_suppresses() {
+        let mut suppressor = suppressor_with(&vec![0.0; 8000]);
+        let mut frame = noise(3, FRAME, 0.5);
+        assert!(!suppressor.process(&mut frame));
+    }
+
+    #[test]
+    fn test_disabled_suppressor_passes_echo() {
+        let played = noise(1, 8000, 0.5);
+        let reference = EchoReference::new(RATE, 1000);
+        reference.write(&played, RATE);
+        let settings = EchoSettings { enabled: false, ..EchoSettings::default() };
+        let mut suppressor = EchoSuppressor::new(settings, reference);
+
+        assert!(!suppressor.process(&mut echo_of(&played, 1600, 0.3)));
+    }
+
+    #[test]
+    fn test_reference_resamples_and_keeps_history() {
+        let reference = EchoReference::new(RATE, 100); // 1600 samples
+        reference.write(&vec![0.25; 4800], 48_000);
+        let latest = reference.latest(10_000);
+        assert_eq!(latest.len(), 1600);
+        // Once the anti-alias filter has settled from the step at the start
+        assert!(latest[200..].iter().all(|&s| (s - 0.25).abs() < 1e-4));
+
+        reference.configure(8_000, 100);
+        assert!(reference.latest(10).is_empty());
+        assert_eq!(reference.sample_rate(), 8_000);
+    }
+}
@@ -0,0 +1,74 @@
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+/// Destination for events meant for the frontend. The app forwards them to
+/// the webview; tests and headless tools collect them from a channel.
+pub trait EventSink: Send + Sync {
+    fn emit_value(&self, event: &str, payload: Value) -> Result<(), String>;
+}
+
+impl dyn EventSink {
+    pub fn emit<T: Serialize>(&self, event: &str, payload: T) -> Result<(), String> {
+        let payload = serde_json::to_value(payload)
+            .map_err(|e| format!("Failed to serialize {} payload: {}", event, e))?;
+        self.emit_value(event, payload)
+    }
+}
+
+/// Drops every event; the default until a real sink is installed.
+#[derive(Default)]
+pub struct NullEventSink;
+
+impl EventSink for NullEventSink {
+    fn emit_value(&self, _event: &str, _payload: Value) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmittedEvent {
+    pub name: String,
+    pub payload: Value,
+}
+
+/// Queues every event on an unbounded channel.
+pub struct ChannelEventSink {
+    tx: mpsc::UnboundedSender<EmittedEvent>,
+}
+
+impl ChannelEventSink {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<EmittedEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+}
+
+impl EventSink for ChannelEventSink {
+    fn emit_value(&self, event: &str, payload: Value) -> Result<(), String> {
+        self.tx
+            .send(EmittedEvent { name: event.to_string(), payload })
+            .map_err(|_| format!("Failed to emit {}: receiver dropped", event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_channel_sink_serializes_payloads() {
+        let (sink, mut rx) = ChannelEventSink::new();
+        let sink: Arc<dyn EventSink> = Arc::new(sink);
+
+        sink.emit("audio-level", 0.5f32).unwrap();
+        sink.emit("websocket-binary", vec![1u8, 2]).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), EmittedEvent { name: "audio-level".to_string(), payload: serde_json::json!(0.5) });
+        assert_eq!(rx.try_recv().unwrap().payload, serde_json::json!([1, 2]));
+
+        drop(rx);
+        assert!(sink.emit("audio-level", 0.0f32).is_err());
+    }
+}
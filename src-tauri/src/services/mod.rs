@@ -1 +1,12 @@
-pub mod audio_service;
\ No newline at end of file
+pub mod audio_frames;
+pub mod audio_meter;
+pub mod audio_pipeline;
+pub mod audio_playback_service;
+pub mod audio_service;
+pub mod audio_source;
+pub mod barge_in;
+pub mod chat;
+pub mod echo;
+pub mod events;
+pub mod vad;
+pub mod wav;
@@ -0,0 +1,334 @@
+use crate::services::audio_meter::{rms, to_dbfs};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Start threshold spans -20 dBFS (sensitivity 0) down to -50 dBFS (sensitivity 1)
+const LOUDEST_START_DBFS: f32 = -20.0;
+const SENSITIVITY_RANGE_DB: f32 = 30.0;
+// Speech continues until the level falls this far below the start threshold
+const HYSTERESIS_DB: f32 = 6.0;
+// Audio kept from before the onset so voiced-only streaming doesn't clip the first syllable
+const PREROLL_MARGIN_MS: u32 = 200;
+
+// Persisted under `AppSettings.audio.vad`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct VadSettings {
+    pub enabled: bool,
+    /// 0.0 (only loud speech) to 1.0 (picks up quiet speech, and more noise).
+    pub sensitivity: f32,
+    /// How long the level must stay above the threshold before speech starts.
+    pub min_speech_ms: u32,
+    /// How long the level must stay below the threshold before speech ends.
+    pub hangover_ms: u32,
+    /// Only stream voiced segments (plus a short pre-roll) to Lily-Core.
+    pub voiced_only: bool,
+}
+
+impl Default for VadSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sensitivity: 0.5,
+            min_speech_ms: 60,
+            hangover_ms: 400,
+            voiced_only: false,
+        }
+    }
+}
+
+impl VadSettings {
+    pub fn start_threshold_dbfs(&self) -> f32 {
+        LOUDEST_START_DBFS - self.sensitivity.clamp(0.0, 1.0) * SENSITIVITY_RANGE_DB
+    }
+
+    pub fn stop_threshold_dbfs(&self) -> f32 {
+        self.start_threshold_dbfs() - HYSTERESIS_DB
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VadTransition {
+    SpeechStart,
+    SpeechEnd { duration_ms: u64 },
+}
+
+/// Energy-based detector with hysteresis (separate start/stop thresholds),
+/// an onset delay and a hangover so short pauses don't end the segment.
+pub struct VoiceActivityDetector {
+    settings: VadSettings,
+    sample_rate: u32,
+    speaking: bool,
+    // Samples spent above (while silent) or below (while speaking) the threshold
+    pending_samples: u64,
+    speech_samples: u64,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(settings: VadSettings, sample_rate: u32) -> Self {
+        Self {
+            settings,
+            sample_rate: sample_rate.max(1),
+            speaking: false,
+            pending_samples: 0,
+            speech_samples: 0,
+        }
+    }
+
+    pub fn is_speaking(&self) -> bool {
+        self.speaking
+    }
+
+    fn ms_to_samples(&self, ms: u32) -> u64 {
+        self.sample_rate as u64 * ms as u64 / 1000
+    }
+
+    pub fn process(&mut self, frame: &[f32]) -> Option<VadTransition> {
+        let level = to_dbfs(rms(frame));
+        let len = frame.len() as u64;
+
+        if !self.speaking {
+            if level >= self.settings.start_threshold_dbfs() {
+                self.pending_samples += len;
+                if self.pending_samples >= self.ms_to_samples(self.settings.min_speech_ms) {
+                    self.speaking = true;
+                    self.speech_samples = self.pending_samples;
+                    self.pending_samples = 0;
+                    return Some(VadTransition::SpeechStart);
+                }
+            } else {
+                self.pending_samples = 0;
+            }
+            return None;
+        }
+
+        self.speech_samples += len;
+        if level >= self.settings.stop_threshold_dbfs() {
+            self.pending_samples = 0;
+            return None;
+        }
+
+        self.pending_samples += len;
+        if self.pending_samples >= self.ms_to_samples(self.settings.hangover_ms) {
+            let duration_ms = self.speech_samples * 1000 / self.sample_rate as u64;
+            self.speaking = false;
+            self.pending_samples = 0;
+            self.speech_samples = 0;
+            return Some(VadTransition::SpeechEnd { duration_ms });
+        }
+        None
+    }
+
+    /// Closes an open segment, e.g. when recording stops mid-utterance.
+    pub fn finish(&mut self) -> Option<VadTransition> {
+        if !self.speaking {
+            return None;
+        }
+        let duration_ms = self.speech_samples * 1000 / self.sample_rate as u64;
+        self.speaking = false;
+        self.pending_samples = 0;
+        self.speech_samples = 0;
+        Some(VadTransition::SpeechEnd { duration_ms })
+    }
+}
+
+/// What to do with one captured frame after voice detection.
+#[derive(Debug, Default, PartialEq)]
+pub struct GateOutput {
+    pub transition: Option<VadTransition>,
+    /// Frames to stream, oldest first (may include buffered pre-roll).
+    pub frames: Vec<Vec<f32>>,
+    pub skipped: usize,
+}
+
+/// Runs the detector over outgoing frames and, in voiced-only mode, holds
+/// back silence while keeping a short pre-roll for the next onset.
+pub struct VoiceGate {
+    detector: Option<VoiceActivityDetector>,
+    voiced_only: bool,
+    preroll: VecDeque<Vec<f32>>,
+    preroll_samples: usize,
+    preroll_limit: usize,
+}
+
+impl VoiceGate {
+    pub fn new(settings: VadSettings, sample_rate: u32) -> Self {
+        let detector = settings
+            .enabled
+            .then(|| VoiceActivityDetector::new(settings, sample_rate));
+        let preroll_ms = settings.min_speech_ms + PREROLL_MARGIN_MS;
+        Self {
+            voiced_only: settings.enabled && settings.voiced_only,
+            detector,
+            preroll: VecDeque::new(),
+            preroll_samples: 0,
+            preroll_limit: (sample_rate as u64 * preroll_ms as u64 / 1000) as usize,
+        }
+    }
+
+    pub fn push(&mut self, frame: Vec<f32>) -> GateOutput {
+        let Some(detector) = self.detector.as_mut() else {
+            return GateOutput { transition: None, frames: vec![frame], skipped: 0 };
+        };
+
+        let transition = detector.process(&frame);
+        if !self.voiced_only {
+            return GateOutput { transition, frames: vec![frame], skipped: 0 };
+        }
+
+        match transition {
+            Some(VadTransition::SpeechStart) => {
+                let mut frames: Vec<Vec<f32>> = self.preroll.drain(..).collect();
+                self.preroll_samples = 0;
+                frames.push(frame);
+                GateOutput { transition, frames, skipped: 0 }
+            }
+            // The frame that ends the hangover still belongs to the segment
+            Some(VadTransition::SpeechEnd { .. }) => GateOutput { transition, frames: vec![frame], skipped: 0 },
+            None if detector.is_speaking() => GateOutput { transition, frames: vec![frame], skipped: 0 },
+            None => GateOutput { transition, frames: Vec::new(), skipped: self.hold(frame) },
+        }
+    }
+
+    pub fn finish(&mut self) -> Option<VadTransition> {
+        self.detector.as_mut().and_then(VoiceActivityDetector::finish)
+    }
+
+    // Buffers a silent frame as pre-roll; returns how many frames fell out of it
+    fn hold(&mut self, frame: Vec<f32>) -> usize {
+        self.preroll_samples += frame.len();
+        self.preroll.push_back(frame);
+
+        let mut skipped = 0;
+        while self.preroll_samples > self.preroll_limit {
+            match self.preroll.pop_front() {
+                Some(oldest) => {
+                    self.preroll_samples -= oldest.len();
+                    skipped += 1;
+                }
+                None => break,
+            }
+        }
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+    const FRAME: usize = 640; // 40 ms
+
+    fn tone(amplitude: f32) -> Vec<f32> {
+        (0..FRAME)
+            .map(|i| amplitude * (i as f32 * 2.0 * std::f32::consts::PI * 220.0 / RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_sensitivity_moves_threshold() {
+        let quiet = VadSettings { sensitivity: 1.0, ..VadSettings::default() };
+        let loud = VadSettings { sensitivity: 0.0, ..VadSettings::default() };
+        assert!(quiet.start_threshold_dbfs() < loud.start_threshold_dbfs());
+        assert_eq!(loud.stop_threshold_dbfs(), loud.start_threshold_dbfs() - HYSTERESIS_DB);
+    }
+
+    #[test]
+    fn test_speech_start_needs_min_duration() {
+        let mut vad = VoiceActivityDetector::new(VadSettings::default(), RATE);
+
+        // A single 40 ms click is shorter than min_speech_ms (60 ms)
+        assert_eq!(vad.process(&tone(0.3)), None);
+        assert_eq!(vad.process(&tone(0.0)), None);
+        assert!(!vad.is_speaking());
+
+        assert_eq!(vad.process(&tone(0.3)), None);
+        assert_eq!(vad.process(&tone(0.3)), Some(VadTransition::SpeechStart));
+        assert!(vad.is_speaking());
+    }
+
+    #[test]
+    fn test_hysteresis_keeps_speech_between_thresholds() {
+        let settings = VadSettings::default();
+        let mut vad = VoiceActivityDetector::new(settings, RATE);
+        vad.process(&tone(0.3));
+        vad.process(&tone(0.3));
+        assert!(vad.is_speaking());
+
+        // Between the stop and start thresholds: not loud enough to start, but speech continues
+        let between = 10f32.powf((settings.start_threshold_dbfs() - HYSTERESIS_DB / 2.0) / 20.0) * 2f32.sqrt();
+        for _ in 0..50 {
+            assert_eq!(vad.process(&tone(between)), None);
+        }
+        assert!(vad.is_speaking());
+    }
+
+    #[test]
+    fn test_hangover_bridges_short_pauses() {
+        let mut vad = VoiceActivityDetector::new(VadSettings::default(), RATE);
+        vad.process(&tone(0.3));
+        vad.process(&tone(0.3));
+
+        // 200 ms pause is shorter than the 400 ms hangover
+        for _ in 0..5 {
+            assert_eq!(vad.process(&tone(0.0)), None);
+        }
+        vad.process(&tone(0.3));
+
+        let mut ended = None;
+        for _ in 0..10 {
+            if let Some(transition) = vad.process(&tone(0.0)) {
+                ended = Some(transition);
+                break;
+            }
+        }
+        // 2 onset + 5 pause + 1 speech + 10 hangover frames of 40 ms
+        assert_eq!(ended, Some(VadTransition::SpeechEnd { duration_ms: 720 }));
+        assert!(!vad.is_speaking());
+    }
+
+    #[test]
+    fn test_finish_closes_open_segment() {
+        let mut vad = VoiceActivityDetector::new(VadSettings::default(), RATE);
+        assert_eq!(vad.finish(), None);
+
+        vad.process(&tone(0.3));
+        vad.process(&tone(0.3));
+        assert_eq!(vad.finish(), Some(VadTransition::SpeechEnd { duration_ms: 80 }));
+        assert!(!vad.is_speaking());
+    }
+
+    #[test]
+    fn test_gate_passes_everything_unless_voiced_only() {
+        let mut gate = VoiceGate::new(VadSettings::default(), RATE);
+        let output = gate.push(tone(0.0));
+        assert_eq!(output.frames.len(), 1);
+        assert_eq!(output.skipped, 0);
+
+        let disabled = VadSettings { enabled: false, voiced_only: true, ..VadSettings::default() };
+        let mut gate = VoiceGate::new(disabled, RATE);
+        assert_eq!(gate.push(tone(0.0)).frames.len(), 1);
+    }
+
+    #[test]
+    fn test_voiced_only_gate_sends_preroll_on_onset() {
+        let settings = VadSettings { voiced_only: true, ..VadSettings::default() };
+        let mut gate = VoiceGate::new(settings, RATE);
+
+        // 1 s of silence: only the 260 ms pre-roll window (6 whole frames) is kept
+        let skipped: usize = (0..25).map(|_| gate.push(tone(0.0)).skipped).sum();
+        assert_eq!(skipped, 25 - 6);
+
+        let first = gate.push(tone(0.3));
+        assert!(first.frames.is_empty());
+        let onset = gate.push(tone(0.3));
+        assert_eq!(onset.transition, Some(VadTransition::SpeechStart));
+        // 5 silent frames + the first voiced frame (both held as pre-roll) + the onset frame
+        assert_eq!(onset.frames.len(), 7);
+        assert_eq!(onset.frames.last(), Some(&tone(0.3)));
+
+        assert_eq!(gate.push(tone(0.3)).frames.len(), 1);
+    }
+}
@@ -0,0 +1,147 @@
+pub(crate) const WAVE_FORMAT_PCM: u16 = 1;
+pub(crate) const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedAudio {
+    /// Interleaved samples in [-1.0, 1.0].
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl DecodedAudio {
+    pub fn duration_ms(&self) -> u64 {
+        let frames = self.samples.len() as u64 / self.channels.max(1) as u64;
+        frames * 1000 / self.sample_rate.max(1) as u64
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Decodes a RIFF/WAVE file (PCM 8/16/24/32-bit, float32, extensible).
+pub fn decode_wav(data: &[u8]) -> Result<DecodedAudio, String> {
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    let mut offset = 12;
+
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let size = read_u32(data, offset + 4).unwrap_or(0) as usize;
+        let body_start = offset + 8;
+        // Streamed WAVs often carry a placeholder data size; use what actually arrived
+        let body_end = body_start.saturating_add(size).min(data.len());
+        let body = &data[body_start..body_end];
+
+        match id {
+            b"fmt " => {
+                let mut tag = read_u16(body, 0).ok_or("Truncated WAV fmt chunk")?;
+                let channels = read_u16(body, 2).ok_or("Truncated WAV fmt chunk")?;
+                let sample_rate = read_u32(body, 4).ok_or("Truncated WAV fmt chunk")?;
+                let bits = read_u16(body, 14).ok_or("Truncated WAV fmt chunk")?;
+                if tag == WAVE_FORMAT_EXTENSIBLE {
+                    // The sub-format GUID starts with the real format tag
+                    tag = read_u16(body, 24).ok_or("Truncated WAV extensible fmt chunk")?;
+                }
+                format = Some((tag, channels, sample_rate, bits));
+            }
+            b"data" => {
+                let (tag, channels, sample_rate, bits) = format.ok_or("WAV data chunk before fmt chunk")?;
+                if channels == 0 || sample_rate == 0 {
+                    return Err("WAV header has zero channels or sample rate".to_string());
+                }
+                return Ok(DecodedAudio {
+                    samples: decode_samples(body, tag, bits)?,
+                    sample_rate,
+                    channels,
+                });
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even length
+        offset = body_start.saturating_add(size + (size & 1));
+    }
+
+    Err("WAV file has no data chunk".to_string())
+}
+
+pub(crate) fn decode_samples(data: &[u8], tag: u16, bits: u16) -> Result<Vec<f32>, String> {
+    let samples = match (tag, bits) {
+        (WAVE_FORMAT_PCM, 8) => data.iter().map(|&b| (b as f32 - 128.0) / 128.0).collect(),
+        (WAVE_FORMAT_PCM, 16) => data
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 24) => data
+            .chunks_exact(3)
+            .map(|b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 32) => data
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        _ => return Err(format!("Unsupported WAV encoding (format {}, {} bits)", tag, bits)),
+    };
+    Ok(samples)
+}
+
+/// Encodes interleaved samples as a 16-bit PCM WAV file.
+pub fn encode_wav(samples: &[f32], sample_rate: u32, channels: u16) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let block_align = channels * 2;
+
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for &sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_wav_round_trips() {
+        let samples = vec![0.0, 0.5, -0.5, 0.25, -1.0, 1.0];
+        let audio = decode_wav(&encode_wav(&samples, 44_100, 2)).unwrap();
+
+        assert_eq!(audio.sample_rate, 44_100);
+        assert_eq!(audio.channels, 2);
+        assert_eq!(audio.samples.len(), samples.len());
+        for (decoded, original) in audio.samples.iter().zip(&samples) {
+            assert!((decoded - original).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_decode_wav_needs_data_chunk() {
+        let mut bytes = encode_wav(&[], 16_000, 1);
+        bytes.truncate(36);
+        assert!(decode_wav(&bytes).is_err());
+    }
+}
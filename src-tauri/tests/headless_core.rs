@@ -0,0 +1,85 @@
+// The core wired together the way the app does it, but without Tauri: audio is
+// captured from a synthetic source and streamed to a loopback Lily-Core.
+use futures_util::{SinkExt, StreamExt};
+use lily_ui_lib::domain::interfaces::WebSocketTrait;
+use lily_ui_lib::domain::models::{ReconnectSettings, ServerSettings};
+use lily_ui_lib::infrastructure::protocol::ClientMessage;
+use lily_ui_lib::infrastructure::websocket::WebSocketService;
+use lily_ui_lib::services::audio_pipeline::StreamSettings;
+use lily_ui_lib::services::audio_source::{SourceFormat, SyntheticSource};
+use lily_ui_lib::services::events::{ChannelEventSink, EmittedEvent};
+use lily_ui_lib::AudioService;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+const MIC: SourceFormat = SourceFormat { sample_rate: 48_000, channels: 2 };
+
+async fn next_event(rx: &mut UnboundedReceiver<EmittedEvent>, name: &str) -> EmittedEvent {
+    timeout(Duration::from_secs(5), async {
+        loop {
+            let event = rx.recv().await.expect("event sink closed");
+            if event.name == name {
+                return event;
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out waiting for {}", name))
+}
+
+#[tokio::test]
+async fn test_websocket_audio_streaming() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // Registers the client, then answers the first audio frame with a transcription
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut handshake = None;
+        while let Some(Ok(message)) = socket.next().await {
+            match message {
+                Message::Text(text) if text.starts_with("register:") => {
+                    socket.send(Message::Text("registered".to_string())).await.unwrap();
+                }
+                Message::Text(text) if text.starts_with("audio_start:") => handshake = Some(text),
+                Message::Binary(frame) => {
+                    let reply = r#"transcription:{"type":"final","text":"hello"}"#;
+                    socket.send(Message::Text(reply.to_string())).await.unwrap();
+                    return (handshake, frame.len());
+                }
+                _ => {}
+            }
+        }
+        panic!("client hung up before streaming audio");
+    });
+
+    let (sink, mut events) = ChannelEventSink::new();
+    let websocket = WebSocketService::new(Arc::new(sink)).with_server_settings(ServerSettings {
+        websocket_url: format!("ws://{}", address),
+        reconnect: ReconnectSettings { max_attempts: Some(0), ..ReconnectSettings::default() },
+        ..ServerSettings::default()
+    });
+    websocket.connect().await.unwrap();
+    next_event(&mut events, "registration").await;
+
+    let audio_service = AudioService::with_source(Arc::new(SyntheticSource::new(MIC).tone(440.0, 0.5, 500)));
+    audio_service.set_frame_output(Arc::new(websocket.clone()));
+    let handshake = ClientMessage::AudioStart(StreamSettings::default().format());
+    websocket.send_message(handshake.to_text()).await.unwrap();
+    audio_service.start_recording().await.unwrap();
+
+    let transcription = next_event(&mut events, "transcription").await;
+    assert_eq!(transcription.payload, serde_json::json!({ "type": "final", "text": "hello" }));
+
+    let (handshake, frame_len) = server.await.unwrap();
+    assert!(handshake.is_some());
+    // 40 ms of 16 kHz PCM16
+    assert_eq!(frame_len, 640 * 2);
+
+    audio_service.stop_recording().await.unwrap();
+    websocket.disconnect().await.unwrap();
+}
@@ -124,7 +124,7 @@ describe('Chat Component', () => {
       if (eventName === 'transcription') {
         // Simulate interim transcription event
         setTimeout(() => {
-          handler({ payload: 'transcription:{"type":"interim","text":"Hello world"}' });
+          handler({ payload: { type: 'interim', text: 'Hello world' } });
         }, 100);
       }
       return Promise.resolve(() => {});
@@ -152,7 +152,7 @@ describe('Chat Component', () => {
     mockListen.mockImplementation((eventName: string, handler: Function) => {
       if (eventName === 'transcription') {
         setTimeout(() => {
-          handler({ payload: 'transcription:{"type":"interim","text":"Hello"}' });
+          handler({ payload: { type: 'interim', text: 'Hello' } });
         }, 100);
       }
       return Promise.resolve(() => {});
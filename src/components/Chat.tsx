@@ -10,6 +10,7 @@ interface Message {
   role: "user" | "assistant";
   content: string;
   timestamp: string;
+  status?: "cancelled";
 }
 
 interface LiveTranscription {
@@ -34,6 +35,10 @@ const Chat: React.FC = () => {
   const [isConnected, setIsConnected] = useState(false);
   const [isRegistered, setIsRegistered] = useState(false);
   const messagesEndRef = useRef<HTMLDivElement>(null);
+  // Saved data the backend had to restore from a backup copy
+  const [storageNotice, setStorageNotice] = useState<string | null>(null);
+  // Id of the chat request in flight, so it can be cancelled
+  const pendingRequestId = useRef<string | null>(null);
 
   // Live transcription state
   const [liveTranscription, setLiveTranscription] = useState<LiveTranscription | null>(null);
@@ -125,7 +130,24 @@ const Chat: React.FC = () => {
     // Set up event listeners for WebSocket binary data (audio) and status
     const unsubscribePromises: Promise<() => void>[] = [];
 
-    // Listen for WebSocket binary events (audio data)
+    // TTS replies are played natively by the Rust backend; track its queue
+    const playbackStartedUnsubscribe = listen('tts-playback-started', (event: { payload: { id: number; duration_ms: number } }) => {
+      setIsPlaying(true);
+      logService.logTTSResponse({ id: event.payload.id, durationMs: event.payload.duration_ms });
+    }).then(unsubscribe => unsubscribe);
+
+    const playbackFinishedUnsubscribe = listen('tts-playback-finished', () => {
+      setIsPlaying(false);
+    }).then(unsubscribe => unsubscribe);
+
+    // The user talked over the reply; the backend already stopped or ducked playback
+    const bargeInUnsubscribe = listen('barge-in', (event: { payload: { mode: 'stop' | 'duck'; playback_id: number | null } }) => {
+      logService.logInfo('User interrupted TTS playback', event.payload);
+    }).then(unsubscribe => unsubscribe);
+
+    unsubscribePromises.push(playbackStartedUnsubscribe, playbackFinishedUnsubscribe, bargeInUnsubscribe);
+
+    // Fallback: the backend forwards audio here only when it has no usable output device
     const audioListenerPromise = listen('websocket-binary', (event: { payload: Uint8Array }) => {
       try {
         // Convert to standard Uint8Array to avoid type issues
@@ -173,63 +195,76 @@ const Chat: React.FC = () => {
 
     unsubscribePromises.push(audioListenerPromise);
 
-    // Listen for audio level events from Rust backend
-    const audioLevelUnsubscribe = listen('audio-level', (event: { payload: number }) => {
-      const rms = event.payload;
-      const isActive = rms > 0.025; // Threshold for voice activity
-      setIsAudioActive(isActive);
+    // Voice activity is detected in the Rust backend (VAD with hysteresis and hangover)
+    const speechStartUnsubscribe = listen('speech-start', () => {
+      setIsAudioActive(true);
     }).then(unsubscribe => unsubscribe);
 
-    unsubscribePromises.push(audioLevelUnsubscribe);
-
-    // Listen for WebSocket messages from Lily-Core
-    const transcriptionUnsubscribe = listen('websocket-message', (event: { payload: string }) => {
-      // Parse the transcription message (format: "transcription:{json}")
-      const messageStr = event.payload;
-      if (messageStr.startsWith('transcription:')) {
-        try {
-          const jsonStr = messageStr.substr(14); // Remove "transcription:" prefix
-          const transcriptionData = JSON.parse(jsonStr);
-          const { type, text } = transcriptionData;
-
-          if (type === 'interim') {
-            // Show interim transcription
-            setLiveTranscription({
-              text: text,
-              isInterim: true,
-              timestamp: new Date().toISOString()
-            });
-          } else if (type === 'final') {
-            // Convert live transcription to final message and clear live transcription
-            if (liveTranscription) {
-              const userMessage: Message = {
-                role: "user",
-                content: text,
-                timestamp: new Date().toISOString(),
-              };
-
-              setMessages((prev) => {
-                const newMessages = [...prev, userMessage];
-                // Save chat history whenever it changes
-                persistenceService.saveChatHistory(newMessages);
-                return newMessages;
-              });
-
-              // Clear live transcription
-              setLiveTranscription(null);
-
-              // Log chat sent event
-              logService.logChatSent(text);
-            }
-          }
-        } catch (error) {
-          console.error("Error parsing transcription message:", error);
+    const speechEndUnsubscribe = listen('speech-end', () => {
+      setIsAudioActive(false);
+    }).then(unsubscribe => unsubscribe);
+
+    unsubscribePromises.push(speechStartUnsubscribe, speechEndUnsubscribe);
+
+    // Listen for typed transcription events from Lily-Core
+    const transcriptionUnsubscribe = listen('transcription', (event: { payload: { type: 'interim' | 'final'; text: string } }) => {
+      const { type, text } = event.payload;
+
+      if (type === 'interim') {
+        // Show interim transcription
+        setLiveTranscription({
+          text: text,
+          isInterim: true,
+          timestamp: new Date().toISOString()
+        });
+      } else if (type === 'final') {
+        // Convert live transcription to final message and clear live transcription
+        if (liveTranscription) {
+          const userMessage: Message = {
+            role: "user",
+            content: text,
+            timestamp: new Date().toISOString(),
+          };
+
+          setMessages((prev) => {
+            const newMessages = [...prev, userMessage];
+            // Save chat history whenever it changes
+            persistenceService.saveChatHistory(newMessages);
+            return newMessages;
+          });
+
+          // Clear live transcription
+          setLiveTranscription(null);
+
+          // Log chat sent event
+          logService.logChatSent(text);
         }
       }
     }).then(unsubscribe => unsubscribe);
 
     unsubscribePromises.push(transcriptionUnsubscribe);
 
+    // Recoveries at startup happen before this listener exists, so they are fetched too
+    const showStorageRecoveries = async () => {
+      try {
+        const recoveries = await invoke<{ file: string; backup: string; error: string }[]>('take_storage_recoveries');
+        if (recoveries?.length) {
+          const files = recoveries.map(recovery => recovery.file).join(", ");
+          setStorageNotice(`${files} could not be read and was restored from a backup. Your most recent changes may be missing.`);
+          logService.logError('Storage restored from backup', recoveries);
+        }
+      } catch (error) {
+        console.error("Error checking storage recoveries:", error);
+      }
+    };
+    showStorageRecoveries();
+
+    const storageRecoveredUnsubscribe = listen('storage-recovered', () => {
+      showStorageRecoveries();
+    }).then(unsubscribe => unsubscribe);
+
+    unsubscribePromises.push(storageRecoveredUnsubscribe);
+
     // Connect WebSocket on mount
     const connectWebSocket = async () => {
       try {
@@ -422,12 +457,15 @@ const Chat: React.FC = () => {
     logService.logChatSent(inputValue);
     setInputValue("");
     setIsLoading(true);
+    const requestId = crypto.randomUUID();
+    pendingRequestId.current = requestId;
 
     try {
       // Send message via Rust command
       const invokeParams = {
         message: inputValue,
         ttsEnabled: ttsEnabled,
+        requestId,
         ...(ttsEnabled ? { ttsParams: ttsParams } : {})
       };
       const data = await invoke<{ response: string; timestamp: string }>('send_chat_message', invokeParams);
@@ -452,13 +490,34 @@ const Chat: React.FC = () => {
         user_id: "default_user"
       });
     } catch (error: unknown) {
+      // Lily-Core errors arrive as `{ kind, message, status? }`
+      const coreError = error as { kind?: string; status?: number } | null;
+      if (coreError?.kind === "cancelled") {
+        const cancelledMessage: Message = {
+          role: "assistant",
+          content: "Request cancelled.",
+          timestamp: new Date().toISOString(),
+          status: "cancelled",
+        };
+        setMessages((prev) => {
+          const newMessages = [...prev, cancelledMessage];
+          persistenceService.saveChatHistory(newMessages);
+          return newMessages;
+        });
+        return;
+      }
+
       console.error("Error sending message:", error);
       
       let errorContent = "Sorry, I encountered an error while processing your request. Please try again.";
       
-      // Check if it's a backend connectivity issue
-      if (error instanceof Error && (error.toString().includes("404") || error.toString().includes("Failed to send request"))) {
-        errorContent = "Backend service is unavailable. Please make sure Lily-Core is running on localhost:8000.";
+      if (coreError?.kind === "network" || (coreError?.kind === "http" && coreError.status === 404)) {
+        const httpBaseUrl = (await persistenceService.loadSettings())?.httpBaseUrl;
+        errorContent = httpBaseUrl
+          ? `Backend service is unavailable. Please make sure Lily-Core is running at ${httpBaseUrl}.`
+          : "Backend service is unavailable. Please make sure Lily-Core is running and the server settings are correct.";
+      } else if (coreError?.kind === "timeout") {
+        errorContent = "Lily-Core took too long to respond. Please try again.";
       } else if (error instanceof Error && error.toString().includes("ttsEnabled")) {
         // Handle parameter serialization issues
         errorContent = "Configuration error. Please check your TTS settings and try again.";
@@ -478,10 +537,21 @@ const Chat: React.FC = () => {
         return newMessages;
       });
     } finally {
+      pendingRequestId.current = null;
       setIsLoading(false);
     }
   };
 
+  // Abort the chat request in flight; sendMessage records the cancellation
+  const cancelMessage = async () => {
+    if (!pendingRequestId.current) return;
+    try {
+      await invoke('cancel_chat_request', { requestId: pendingRequestId.current });
+    } catch (error) {
+      console.error("Error cancelling chat request:", error);
+    }
+  };
+
   // Handle form submission
   const handleSubmit = (e: React.FormEvent) => {
     e.preventDefault();
@@ -504,6 +574,9 @@ const Chat: React.FC = () => {
       // Clear persisted chat history
       persistenceService.clearChatHistory();
       // Stop any playing audio
+      invoke('stop_tts_playback').catch(error => {
+        console.error("Error stopping TTS playback:", error);
+      });
       if (currentAudio) {
         currentAudio.pause();
         setCurrentAudio(null);
@@ -757,6 +830,12 @@ const Chat: React.FC = () => {
       )}
       
       <div className="messages-container">
+        {storageNotice && (
+          <div className="storage-notice">
+            <span>{storageNotice}</span>
+            <button type="button" onClick={() => setStorageNotice(null)}>Dismiss</button>
+          </div>
+        )}
         {messages.length === 0 ? (
           <div className="welcome-message">
             <h2>Welcome to Lily AI!</h2>
@@ -775,7 +854,7 @@ const Chat: React.FC = () => {
           </div>
         ) : (
           messages.map((message, index) => (
-            <div key={index} className={`message ${message.role}`}>
+            <div key={index} className={`message ${message.role}${message.status ? ` ${message.status}` : ''}`}>
               <div className="message-content">
                 <div className="message-text">{message.content}</div>
                 <div className="message-time">
@@ -809,6 +888,9 @@ const Chat: React.FC = () => {
                 <span></span>
                 <span></span>
               </div>
+              <button type="button" className="cancel-request-button" onClick={cancelMessage}>
+                Stop
+              </button>
             </div>
           </div>
         )}
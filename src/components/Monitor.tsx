@@ -4,6 +4,7 @@ import ServiceCard from "./ServiceCard";
 import MCPServiceCard from "./MCPServiceCard";
 import logService from "../services/LogService";
 import webSocketService from "../services/WebSocketService";
+import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 
 interface ServiceStatus {
@@ -113,13 +114,8 @@ const Monitor: React.FC = () => {
   const fetchMonitoringData = async () => {
     setLoading(true);
     try {
-      const response = await fetch("http://localhost:8000/monitoring");
-      
-      if (!response.ok) {
-        throw new Error(`HTTP error! status: ${response.status}`);
-      }
-      
-      const data: MonitoringData = await response.json();
+      // Goes through the Rust client so the configured Lily-Core URL and timeouts apply
+      const data = await invoke<MonitoringData>("get_monitoring_data");
       // Only update state if data has changed to prevent unnecessary re-renders
       if (JSON.stringify(data) !== JSON.stringify(monitoringData)) {
         setMonitoringData(data);
@@ -170,13 +166,7 @@ const Monitor: React.FC = () => {
     }
     
     try {
-      const response = await fetch("http://localhost:8000/agent-loops");
-      
-      if (!response.ok) {
-        throw new Error(`HTTP error! status: ${response.status}`);
-      }
-      
-      const data: AgentLoop = await response.json();
+      const data = await invoke<AgentLoop>("get_agent_loop");
       console.log("Agent loop data fetched:", {
         exists: data.exists,
         steps: data.steps?.length,
@@ -8,9 +8,16 @@ interface TTSParameters {
   lang: string;
 }
 
+interface ServerSettings {
+  websocket_url: string;
+  http_base_url: string;
+  path_prefix?: string | null;
+}
+
 interface AppSettings {
   tts_params: TTSParameters;
   tts_enabled: boolean;
+  server?: ServerSettings;
   input_device_id?: string;
   output_device_id?: string;
 }
@@ -24,7 +31,10 @@ class PersistenceService {
     outputDeviceId?: string
   ): Promise<void> {
     try {
+      // Keep sections this screen doesn't edit (e.g. server endpoints) intact
+      const current = await invoke<AppSettings>('load_settings').catch(() => null);
       const settings: AppSettings = {
+        ...current,
         tts_params: ttsParams,
         tts_enabled: ttsEnabled,
         input_device_id: inputDeviceId,
@@ -43,6 +53,7 @@ class PersistenceService {
     ttsEnabled: boolean;
     inputDeviceId?: string;
     outputDeviceId?: string;
+    httpBaseUrl?: string;
   } | null> {
     try {
       const settings = await invoke<AppSettings>('load_settings');
@@ -50,7 +61,8 @@ class PersistenceService {
         ttsParams: settings.tts_params,
         ttsEnabled: settings.tts_enabled,
         inputDeviceId: settings.input_device_id,
-        outputDeviceId: settings.output_device_id
+        outputDeviceId: settings.output_device_id,
+        httpBaseUrl: settings.server?.http_base_url
       };
     } catch (error) {
       console.error('Failed to load settings:', error);
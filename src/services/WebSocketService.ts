@@ -49,6 +49,16 @@ class WebSocketService {
       });
       this.unsubscribeFunctions.push(unsubscribeMessage);
 
+      const unsubscribeServerError = await listen('server-error', (event: any) => {
+        console.error("WebSocketService: Server error:", event.payload);
+        logService.logError("WebSocketService: Server error", {
+          message: event.payload?.message,
+          code: event.payload?.code,
+          timestamp: new Date().toISOString()
+        });
+      });
+      this.unsubscribeFunctions.push(unsubscribeServerError);
+
       const unsubscribeBinary = await listen('websocket-binary', (event: any) => {
         console.log("WebSocketService: Binary data received");
         logService.logInfo("WebSocketService: Binary data received", {
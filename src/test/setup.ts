@@ -22,6 +22,7 @@ vi.mock('@tauri-apps/api/core', () => ({
           tts_enabled: true
         });
       case 'load_chat_history':
+      case 'take_storage_recoveries':
         return Promise.resolve([]);
       case 'get_websocket_status':
         return Promise.resolve({ connected: false, registered: false });
@@ -34,9 +35,11 @@ vi.mock('@tauri-apps/api/core', () => ({
       case 'disconnect_websocket':
       case 'send_websocket_message':
       case 'send_chat_message':
+      case 'cancel_chat_request':
       case 'get_conversation_history':
       case 'clear_conversation':
       case 'get_monitoring_data':
+      case 'get_agent_loop':
       case 'send_websocket_audio':
       case 'start_audio_recording':
       case 'stop_audio_recording':